@@ -1 +1,20 @@
-import("../pkg/index.js").catch(console.error);
+import("../pkg/index.js")
+  .then((wasm) => {
+    const config = new wasm.GameConfig();
+    const params = new URLSearchParams(window.location.search);
+    const seed = params.get("seed");
+    if (seed !== null) {
+      config.seed = BigInt(seed);
+    }
+    const tickRate = params.get("tickRate");
+    if (tickRate !== null) {
+      config.tick_rate = Number(tickRate);
+    }
+    config.on_error = (kind, message) => {
+      console.error(`Walk the Dog failed to start (${kind}): ${message}`);
+    };
+    // Exposed so a replay can be grabbed from the console with
+    // `walkTheDog.replay()` and attached to a bug report.
+    window.walkTheDog = wasm.run(config);
+  })
+  .catch(console.error);
@@ -1,3 +1,4 @@
+use crate::engine::EngineError;
 use anyhow::{anyhow, Result};
 use js_sys::ArrayBuffer;
 use std::future::Future;
@@ -6,7 +7,7 @@ use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
     CanvasRenderingContext2d, Document, Element, HtmlCanvasElement, HtmlElement, HtmlImageElement,
-    Response, Window,
+    KeyboardEvent, KeyboardEventInit, Response, Window,
 };
 
 macro_rules! log {
@@ -28,19 +29,19 @@ macro_rules! panic {
 }
 
 pub fn window() -> Result<Window> {
-    web_sys::window().ok_or_else(|| anyhow!("no window found :S"))
+    web_sys::window().ok_or_else(|| EngineError::Dom("no window found :S".to_string()).into())
 }
 
 pub fn document() -> Result<Document> {
     window()?
         .document()
-        .ok_or_else(|| anyhow!("no document found :S"))
+        .ok_or_else(|| EngineError::Dom("no document found :S".to_string()).into())
 }
 
 pub fn canvas() -> Result<HtmlCanvasElement> {
     document()?
         .get_element_by_id("canvas")
-        .ok_or_else(|| anyhow!("error getting canvas element"))?
+        .ok_or_else(|| EngineError::Dom("error getting canvas element".to_string()))?
         .dyn_into::<HtmlCanvasElement>()
         .map_err(|element| anyhow!("error converting {:#?} to HtmlCanvasElement", element))
 }
@@ -58,7 +59,7 @@ pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
     document()
         .and_then(|doc| {
             doc.get_element_by_id(id)
-                .ok_or_else(|| anyhow!("Element with id {} not found", id))
+                .ok_or_else(|| EngineError::Dom(format!("Element with id {} not found", id)).into())
         })
         .and_then(|element| {
             element
@@ -70,7 +71,7 @@ pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
 fn find_ui() -> Result<Element> {
     document().and_then(|doc| {
         doc.get_element_by_id("ui")
-            .ok_or_else(|| anyhow!("UI element not found"))
+            .ok_or_else(|| EngineError::Dom("UI element not found".to_string()).into())
     })
 }
 
@@ -80,6 +81,23 @@ pub fn draw_ui(html: &str) -> Result<()> {
         .map_err(|err| anyhow!("Could not insert html {:#?}", err))
 }
 
+// Presses and lets go of `code` as if it were typed, for buttons that stand
+// in for keys.
+pub fn tap_key(code: &str) -> Result<()> {
+    let window = window()?;
+    for kind in ["keydown", "keyup"] {
+        let event = KeyboardEvent::new_with_keyboard_event_init_dict(
+            kind,
+            KeyboardEventInit::new().code(code),
+        )
+        .map_err(|err| anyhow!("Could not create {} event {:#?}", kind, err))?;
+        window
+            .dispatch_event(&event)
+            .map_err(|err| anyhow!("Could not dispatch {} event {:#?}", kind, err))?;
+    }
+    Ok(())
+}
+
 pub fn hide_ui() -> Result<()> {
     let ui = find_ui()?;
 
@@ -104,26 +122,38 @@ where
     wasm_bindgen_futures::spawn_local(future);
 }
 
+fn fetch_error(resource: &str, message: String) -> EngineError {
+    EngineError::Fetch {
+        resource: resource.to_string(),
+        message,
+    }
+}
+
 pub async fn fetch_with_str(resource: &str) -> Result<JsValue> {
     JsFuture::from(window()?.fetch_with_str(resource))
         .await
-        .map_err(|err| anyhow!("error fetching {:#?}", err))
+        .map_err(|err| fetch_error(resource, format!("{:#?}", err)).into())
 }
 pub async fn fetch_response(resource: &str) -> Result<Response> {
-    fetch_with_str(resource)
+    let response: Response = fetch_with_str(resource)
         .await?
         .dyn_into()
-        .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))
+        .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))?;
+    if !response.ok() {
+        return Err(fetch_error(resource, format!("HTTP status {}", response.status())).into());
+    }
+    Ok(response)
 }
 
 pub async fn fetch_json(json_path: &str) -> Result<JsValue> {
     let resp = fetch_response(json_path).await?;
-    JsFuture::from(
-        resp.json()
-            .map_err(|err| anyhow!("Could not get JSON from response {:#?}", err))?,
-    )
-    .await
-    .map_err(|err| anyhow!("error fetching JSON {:#?}", err))
+    let parse_error = |err| EngineError::Parse {
+        resource: json_path.to_string(),
+        message: format!("{:#?}", err),
+    };
+    JsFuture::from(resp.json().map_err(parse_error)?)
+        .await
+        .map_err(|err| parse_error(err).into())
 }
 
 pub fn new_image() -> Result<HtmlImageElement> {
@@ -134,11 +164,11 @@ pub async fn fetch_array_buffer(resource: &str) -> Result<ArrayBuffer> {
     let array_buffer = fetch_response(resource)
         .await?
         .array_buffer()
-        .map_err(|err| anyhow!("Error loading array buffer {:#?}", err))?;
+        .map_err(|err| fetch_error(resource, format!("{:#?}", err)))?;
 
     JsFuture::from(array_buffer)
         .await
-        .map_err(|err| anyhow!("Error converting array buffer into a future {:#?}", err))?
+        .map_err(|err| fetch_error(resource, format!("{:#?}", err)))?
         .dyn_into()
         .map_err(|err| anyhow!("Error converting raw JSValue to ArrayBuffer {:#?}", err))
 }
@@ -158,6 +188,12 @@ pub fn request_animation_frame(callback: &LoopClosure) -> Result<i32> {
         .map_err(|err| anyhow!("Cannot request animation frame {:#?}", err))
 }
 
+pub fn cancel_animation_frame(handle: i32) -> Result<()> {
+    window()?
+        .cancel_animation_frame(handle)
+        .map_err(|err| anyhow!("Cannot cancel animation frame {:#?}", err))
+}
+
 pub fn create_raf_closure(f: impl FnMut(f64) + 'static) -> LoopClosure {
     closure_wrap(Box::new(f))
 }
@@ -0,0 +1,71 @@
+use crate::engine::LoopConfig;
+use js_sys::Function;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct GameConfig {
+    seed: Option<u64>,
+    replay: Option<Vec<u8>>,
+    tick_rate: Option<f32>,
+    max_catch_up_ticks: Option<u32>,
+    on_error: Option<Function>,
+}
+
+#[wasm_bindgen]
+impl GameConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        GameConfig::default()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_replay(&mut self, replay: Vec<u8>) {
+        self.replay = Some(replay);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tick_rate(&mut self, tick_rate: f32) {
+        self.tick_rate = Some(tick_rate);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_catch_up_ticks(&mut self, ticks: u32) {
+        self.max_catch_up_ticks = Some(ticks);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_on_error(&mut self, on_error: Function) {
+        self.on_error = Some(on_error);
+    }
+}
+
+impl GameConfig {
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn replay(&self) -> Option<&[u8]> {
+        self.replay.as_deref()
+    }
+
+    pub fn on_error(&self) -> Option<&Function> {
+        self.on_error.as_ref()
+    }
+
+    pub fn loop_config(&self) -> LoopConfig {
+        let mut config = LoopConfig::default();
+        if let Some(tick_rate) = self.tick_rate.filter(|tick_rate| *tick_rate > 0.0) {
+            config.tick_rate = tick_rate;
+        }
+        if let Some(ticks) = self.max_catch_up_ticks {
+            config.max_catch_up_ticks = ticks;
+        }
+        config
+    }
+}
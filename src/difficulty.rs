@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::rc::Rc;
+
+// One step of the difficulty curve, which takes over once the boy has run
+// `distance` pixels.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Level {
+    pub distance: i32,
+    pub running_speed: i32,
+    pub obstacle_buffer: i32,
+}
+
+#[derive(Deserialize)]
+pub struct DifficultyCurve {
+    levels: Vec<Level>,
+}
+
+pub struct Difficulty {
+    curve: Rc<DifficultyCurve>,
+    level: usize,
+}
+
+impl DifficultyCurve {
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    pub fn check(&self) -> Result<()> {
+        match self.levels.first() {
+            Some(first) if first.distance <= 0 => {}
+            _ => return Err(anyhow!("The difficulty curve needs a level at distance 0")),
+        }
+        if self
+            .levels
+            .windows(2)
+            .any(|pair| pair[0].distance >= pair[1].distance)
+        {
+            return Err(anyhow!("Difficulty levels must get further apart"));
+        }
+        if self.levels.iter().any(|level| level.running_speed <= 0) {
+            return Err(anyhow!("Every difficulty level needs a running speed"));
+        }
+        Ok(())
+    }
+}
+
+impl Difficulty {
+    pub fn new(curve: Rc<DifficultyCurve>) -> Self {
+        Difficulty { curve, level: 0 }
+    }
+
+    pub fn curve(&self) -> Rc<DifficultyCurve> {
+        self.curve.clone()
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level as u32
+    }
+
+    pub fn running_speed(&self) -> i32 {
+        self.current().running_speed
+    }
+
+    pub fn obstacle_buffer(&self) -> i32 {
+        self.current().obstacle_buffer
+    }
+
+    // Moves on to the furthest level the boy has reached and says whether
+    // that changed anything.
+    pub fn update(&mut self, distance: i32) -> bool {
+        let level = self
+            .curve
+            .levels
+            .iter()
+            .rposition(|level| level.distance <= distance)
+            .unwrap_or(0);
+        let changed = level > self.level;
+        self.level = self.level.max(level);
+        changed
+    }
+
+    fn current(&self) -> &Level {
+        &self.curve.levels[self.level]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_follow_the_distance_run() {
+        let curve: DifficultyCurve = serde_json::from_str(
+            r#"{"levels": [
+                {"distance": 0, "runningSpeed": 4, "obstacleBuffer": 20},
+                {"distance": 1000, "runningSpeed": 5, "obstacleBuffer": 60},
+                {"distance": 3000, "runningSpeed": 6, "obstacleBuffer": 120}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(curve.check().is_ok());
+        let mut difficulty = Difficulty::new(Rc::new(curve));
+
+        let changes: Vec<(bool, u32, i32, i32)> = [-20, 999, 1000, 2000, 5000]
+            .iter()
+            .map(|distance| {
+                let changed = difficulty.update(*distance);
+                (
+                    changed,
+                    difficulty.level(),
+                    difficulty.running_speed(),
+                    difficulty.obstacle_buffer(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                (false, 0, 4, 20),
+                (false, 0, 4, 20),
+                (true, 1, 5, 60),
+                (false, 1, 5, 60),
+                (true, 2, 6, 120),
+            ]
+        );
+        let unordered: DifficultyCurve = serde_json::from_str(
+            r#"{"levels": [
+                {"distance": 0, "runningSpeed": 4, "obstacleBuffer": 20},
+                {"distance": 0, "runningSpeed": 5, "obstacleBuffer": 20}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(unordered.check().is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::engine::{Animations, Animator, Clip, FrameEvent, PlayMode};
+use std::rc::Rc;
+
+impl Animations {
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.get(name)
+    }
+}
+
+impl Clip {
+    fn length(&self) -> u32 {
+        self.frames.len() as u32 * self.frame_duration.max(1)
+    }
+
+    fn index(&self, tick: u32) -> Option<usize> {
+        let count = self.frames.len() as u32;
+        if count == 0 {
+            return None;
+        }
+        let step = self.step(tick);
+        let index = match self.mode {
+            PlayMode::Loop => step % count,
+            PlayMode::Once => step.min(count - 1),
+            PlayMode::PingPong if count == 1 => 0,
+            PlayMode::PingPong => {
+                let period = 2 * count - 2;
+                let step = step % period;
+                if step < count {
+                    step
+                } else {
+                    period - step
+                }
+            }
+        };
+        Some(index as usize)
+    }
+
+    fn step(&self, tick: u32) -> u32 {
+        tick / self.frame_duration.max(1)
+    }
+
+    // Events belong to the tick a frame first shows on, and a one-shot clip
+    // holding its last frame doesn't repeat them.
+    fn events_at(&self, tick: u32) -> impl Iterator<Item = &FrameEvent> {
+        let starts_frame = self.step(tick) * self.frame_duration.max(1) == tick
+            && !(self.mode == PlayMode::Once && self.step(tick) >= self.frames.len() as u32);
+        let index = self.index(tick).filter(|_| starts_frame);
+        self.events
+            .iter()
+            .filter(move |event| Some(event.frame) == index)
+    }
+
+    pub fn frame(&self, tick: u32) -> Option<&str> {
+        self.index(tick).map(|index| self.frames[index].as_str())
+    }
+}
+
+impl Animator {
+    pub fn new(animations: Rc<Animations>) -> Self {
+        Animator {
+            animations,
+            tick: 0,
+            carry: 0.0,
+            events: vec![],
+        }
+    }
+
+    pub fn animations(&self) -> Rc<Animations> {
+        self.animations.clone()
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn carry(&self) -> f32 {
+        self.carry
+    }
+
+    pub fn reset(&mut self) {
+        self.tick = 0;
+        self.carry = 0.0;
+    }
+
+    // Frame durations are counted in ticks at the default rate, so a longer
+    // or shorter tick moves the clip on by `ticks` of them, a whole one at a
+    // time.
+    pub fn update(&mut self, clip: &str, ticks: f32) {
+        let elapsed = self.carry + ticks;
+        let whole = elapsed.floor();
+        self.carry = elapsed - whole;
+        for _ in 0..whole as u32 {
+            if let Some(clip) = self.animations.clip(clip) {
+                self.events
+                    .extend(clip.events_at(self.tick).map(|event| event.name.clone()));
+            }
+            self.tick = self.tick.wrapping_add(1);
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn frame(&self, clip: &str) -> Option<&str> {
+        self.animations.clip(clip)?.frame(self.tick)
+    }
+
+    // A one-shot clip is finished while its last tick is showing, so the
+    // state driving it can move on without holding the final frame twice.
+    pub fn finished(&self, clip: &str) -> bool {
+        match self.animations.clip(clip) {
+            Some(clip) => clip.mode == PlayMode::Once && self.tick + 1 >= clip.length(),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(json: &str, ticks: u32) -> (Vec<String>, bool) {
+        let animations: Animations = serde_json::from_str(json).unwrap();
+        let mut animator = Animator::new(Rc::new(animations));
+        let mut frames = vec![];
+        for _ in 0..ticks {
+            frames.push(animator.frame("Clip").unwrap().to_string());
+            animator.update("Clip", 1.0);
+        }
+        (frames, animator.finished("Clip"))
+    }
+
+    #[test]
+    fn clips_loop_play_once_or_ping_pong() {
+        let clip = |mode| {
+            format!(
+                r#"{{"Clip": {{"frames": ["a", "b", "c"], "frameDuration": 2, "mode": "{}"}}}}"#,
+                mode
+            )
+        };
+
+        assert_eq!(
+            frames(&clip("loop"), 8),
+            (
+                ["a", "a", "b", "b", "c", "c", "a", "a"]
+                    .map(String::from)
+                    .to_vec(),
+                false
+            )
+        );
+        assert_eq!(
+            frames(&clip("once"), 8),
+            (
+                ["a", "a", "b", "b", "c", "c", "c", "c"]
+                    .map(String::from)
+                    .to_vec(),
+                true
+            )
+        );
+        assert_eq!(
+            frames(&clip("ping-pong"), 10),
+            (
+                ["a", "a", "b", "b", "c", "c", "b", "b", "a", "a"]
+                    .map(String::from)
+                    .to_vec(),
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn events_fire_once_when_their_frame_starts() {
+        let animations: Animations = serde_json::from_str(
+            r#"{"Dead": {
+                "frames": ["a", "b", "c"],
+                "frameDuration": 2,
+                "mode": "once",
+                "events": [{"frame": 0, "name": "fall"}, {"frame": 2, "name": "impact"}]
+            }}"#,
+        )
+        .unwrap();
+        let mut animator = Animator::new(Rc::new(animations));
+
+        let events: Vec<Vec<String>> = (0..8)
+            .map(|_| {
+                animator.update("Dead", 1.0);
+                animator.take_events()
+            })
+            .collect();
+
+        let fired =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        assert_eq!(
+            events,
+            vec![
+                fired(&["fall"]),
+                fired(&[]),
+                fired(&[]),
+                fired(&[]),
+                fired(&["impact"]),
+                fired(&[]),
+                fired(&[]),
+                fired(&[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn shorter_ticks_show_each_frame_for_the_same_time() {
+        let json = r#"{"Clip": {"frames": ["a", "b"], "frameDuration": 2, "mode": "loop"}}"#;
+        let animations: Animations = serde_json::from_str(json).unwrap();
+        let mut animator = Animator::new(Rc::new(animations));
+        let mut frames = vec![];
+        for _ in 0..8 {
+            frames.push(animator.frame("Clip").unwrap().to_string());
+            animator.update("Clip", 0.5);
+        }
+        assert_eq!(frames, ["a", "a", "a", "a", "b", "b", "b", "b"]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::browser;
+use crate::engine::image::load_image;
+use crate::engine::{
+    AssetEvent, AssetManifest, Assets, Audio, EngineError, ImageElement, Sheet, Sound, SpriteSheet,
+};
+use anyhow::{anyhow, Context, Result};
+use futures::channel::mpsc::UnboundedSender;
+use futures::future::{join3, join_all};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+
+impl Assets {
+    pub async fn load(
+        manifest_path: &str,
+        audio: &Audio,
+        progress: &UnboundedSender<AssetEvent>,
+    ) -> Result<Self> {
+        let manifest = browser::fetch_json(manifest_path).await?;
+        let manifest: AssetManifest =
+            serde_wasm_bindgen::from_value(manifest).map_err(|err| EngineError::Parse {
+                resource: manifest_path.to_string(),
+                message: err.to_string(),
+            })?;
+        Assets::load_manifest(&manifest, audio, progress).await
+    }
+
+    pub async fn load_manifest(
+        manifest: &AssetManifest,
+        audio: &Audio,
+        progress: &UnboundedSender<AssetEvent>,
+    ) -> Result<Self> {
+        let image_paths = unique_paths(
+            manifest.images.values().chain(
+                manifest
+                    .sprite_sheets
+                    .values()
+                    .map(|sprite_sheet| &sprite_sheet.image),
+            ),
+        );
+        let json_paths = unique_paths(
+            manifest.json.values().chain(
+                manifest
+                    .sprite_sheets
+                    .values()
+                    .map(|sprite_sheet| &sprite_sheet.json),
+            ),
+        );
+        let sound_paths = unique_paths(manifest.sounds.values());
+        send(
+            progress,
+            AssetEvent::Queued(image_paths.len() + json_paths.len() + sound_paths.len()),
+        );
+
+        // Every file is requested up front and awaited together, so a
+        // manifest loads in roughly the time of its slowest asset.
+        let (images, json, sounds) = join3(
+            load_all(&image_paths, progress, load_image),
+            load_all(&json_paths, progress, browser::fetch_json),
+            load_all(&sound_paths, progress, |path| audio.load_sound(path)),
+        )
+        .await;
+        let (images, json, sounds) = (images?, json?, sounds?);
+
+        let mut sprite_sheets = HashMap::new();
+        for (key, sprite_sheet) in &manifest.sprite_sheets {
+            let sheet: Sheet = serde_wasm_bindgen::from_value(json[&sprite_sheet.json].clone())
+                .map_err(|err| EngineError::Parse {
+                    resource: sprite_sheet.json.clone(),
+                    message: err.to_string(),
+                })?;
+            if let Some(meta) = sheet
+                .meta
+                .as_ref()
+                .filter(|meta| meta.image != sprite_sheet.image)
+            {
+                log!(
+                    "{} was packed for {} but is drawn from {}",
+                    sprite_sheet.json,
+                    meta.image,
+                    sprite_sheet.image
+                );
+            }
+            sprite_sheets.insert(
+                key.clone(),
+                Rc::new(SpriteSheet::new(sheet, images[&sprite_sheet.image].clone())),
+            );
+        }
+
+        Ok(Assets {
+            images: by_key(&manifest.images, &images),
+            sprite_sheets,
+            sounds: by_key(&manifest.sounds, &sounds),
+            json: by_key(&manifest.json, &json),
+        })
+    }
+
+    pub fn image(&self, key: &str) -> Result<ImageElement> {
+        self.images
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("No image named {} in the manifest", key))
+    }
+
+    pub fn sprite_sheet(&self, key: &str) -> Result<Rc<SpriteSheet>> {
+        self.sprite_sheets
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("No sprite sheet named {} in the manifest", key))
+    }
+
+    pub fn sound(&self, key: &str) -> Result<Sound> {
+        self.sounds
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("No sound named {} in the manifest", key))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        let value = self
+            .json
+            .get(key)
+            .ok_or_else(|| anyhow!("No JSON named {} in the manifest", key))?;
+        serde_wasm_bindgen::from_value(value.clone()).map_err(|err| {
+            EngineError::Parse {
+                resource: key.to_string(),
+                message: err.to_string(),
+            }
+            .into()
+        })
+    }
+}
+
+fn unique_paths<'a>(paths: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut unique: Vec<String> = paths.cloned().collect();
+    unique.sort();
+    unique.dedup();
+    unique
+}
+
+async fn load_all<'a, T, F, Fut>(
+    paths: &'a [String],
+    progress: &UnboundedSender<AssetEvent>,
+    load: F,
+) -> Result<HashMap<String, T>>
+where
+    F: Fn(&'a str) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let loaded = join_all(paths.iter().map(|path| {
+        let asset = load(path);
+        async move {
+            let asset = asset.await;
+            match &asset {
+                Ok(_) => send(progress, AssetEvent::Loaded),
+                Err(err) => {
+                    error!("Could not load {} {:#?}", path, err);
+                    send(progress, AssetEvent::Failed(path.clone()))
+                }
+            }
+            asset
+        }
+    }))
+    .await;
+    paths
+        .iter()
+        .cloned()
+        .zip(loaded)
+        .map(|(path, asset)| {
+            asset
+                .map(|asset| (path.clone(), asset))
+                .with_context(|| format!("Could not load {}", path))
+        })
+        .collect()
+}
+
+fn send(progress: &UnboundedSender<AssetEvent>, event: AssetEvent) {
+    if let Err(err) = progress.unbounded_send(event) {
+        error!("Could not report asset progress {:#?}", err);
+    }
+}
+
+fn by_key<T: Clone>(
+    keys: &HashMap<String, String>,
+    loaded: &HashMap<String, T>,
+) -> HashMap<String, T> {
+    keys.iter()
+        .map(|(key, path)| (key.clone(), loaded[path].clone()))
+        .collect()
+}
@@ -1,27 +1,109 @@
 use crate::browser;
-use crate::engine::{Audio, Sound};
+use crate::engine::{Audio, EngineError, Sound};
 use crate::sound;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::rc::Rc;
+use web_sys::AudioBufferSourceNode;
+#[cfg(test)]
+use web_sys::AudioContextState;
 
 impl Audio {
     pub fn new() -> Result<Self> {
         let context = sound::create_audio_context()?;
-        Ok(Audio { context })
+        Ok(Audio {
+            context: Some(context),
+            looping: Rc::new(RefCell::new(vec![])),
+        })
+    }
+
+    pub fn headless() -> Self {
+        Audio {
+            context: None,
+            looping: Rc::new(RefCell::new(vec![])),
+        }
     }
 
     pub async fn load_sound(&self, filename: &str) -> Result<Sound> {
+        let context = self
+            .context
+            .as_ref()
+            .ok_or_else(|| anyhow!("Cannot load {} without an audio context", filename))?;
         let array_buffer = browser::fetch_array_buffer(filename).await?;
-        let audio_buffer = sound::decode_audio_data(&self.context, &array_buffer).await?;
+        let audio_buffer = sound::decode_audio_data(context, &array_buffer)
+            .await
+            .map_err(|err| EngineError::Decode {
+                resource: filename.to_string(),
+                message: format!("{:#}", err),
+            })?;
         Ok(Sound {
-            buffer: audio_buffer,
+            buffer: Some(audio_buffer),
         })
     }
 
     pub fn play_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::NO, 1.0)
+        self.play(sound, sound::LOOPING::NO, 1.0).map(|_track| ())
     }
 
     pub fn play_looping_sound(&self, sound: &Sound, volume: f32) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::Yes, volume)
+        if let Some(track) = self.play(sound, sound::LOOPING::Yes, volume)? {
+            self.looping.borrow_mut().push(track);
+        }
+        Ok(())
+    }
+
+    pub fn suspend(&self) -> Result<()> {
+        match &self.context {
+            Some(context) => sound::suspend_audio_context(context),
+            None => Ok(()),
+        }
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        match &self.context {
+            Some(context) => sound::resume_audio_context(context),
+            None => Ok(()),
+        }
+    }
+
+    // Stops the looping sounds and lets go of the audio context, after which
+    // nothing more can be played.
+    pub fn close(&self) -> Result<()> {
+        for track in self.looping.borrow_mut().drain(..) {
+            sound::stop_sound(&track)?;
+        }
+        match &self.context {
+            Some(context) => sound::close_audio_context(context),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn running(&self) -> bool {
+        let open = matches!(
+            &self.context,
+            Some(context) if context.state() != AudioContextState::Closed
+        );
+        open || !self.looping.borrow().is_empty()
+    }
+
+    fn play(
+        &self,
+        sound: &Sound,
+        looping: sound::LOOPING,
+        volume: f32,
+    ) -> Result<Option<AudioBufferSourceNode>> {
+        match (&self.context, &sound.buffer) {
+            (Some(context), Some(buffer)) => {
+                sound::play_sound(context, buffer, looping, volume).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Sound {
+    pub fn headless() -> Self {
+        Sound { buffer: None }
     }
 }
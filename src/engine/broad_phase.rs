@@ -0,0 +1,97 @@
+use crate::engine::{BroadPhase, Rect};
+use std::collections::VecDeque;
+
+impl<T> Default for BroadPhase<T> {
+    fn default() -> Self {
+        BroadPhase {
+            entries: VecDeque::new(),
+            widest: 0,
+        }
+    }
+}
+
+impl<T> BroadPhase<T> {
+    pub fn insert(&mut self, bounds: Rect, item: T) {
+        self.widest = self.widest.max(bounds.width);
+        let index = self
+            .entries
+            .partition_point(|(entry, _)| entry.x() <= bounds.x());
+        self.entries.insert(index, (bounds, item));
+    }
+
+    // Everything whose bounds touch `area`. Nothing can start further left
+    // than the widest entry would reach, so only that stretch is searched.
+    pub fn query(&self, area: &Rect) -> impl Iterator<Item = &T> {
+        let start = self
+            .entries
+            .partition_point(|(entry, _)| entry.x() < area.x() - self.widest);
+        let end = self
+            .entries
+            .partition_point(|(entry, _)| entry.x() <= area.right());
+        let area = area.clone();
+        self.entries
+            .range(start..end.max(start))
+            .filter(move |(entry, _)| {
+                entry.x() <= area.right()
+                    && entry.right() >= area.x()
+                    && entry.y() <= area.bottom()
+                    && entry.bottom() >= area.y()
+            })
+            .map(|(_, item)| item)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|(_, item)| item)
+    }
+
+    // Drops everything that ends at or before `x`, which only means looking
+    // at the entries that start before it.
+    pub fn remove_left_of(&mut self, x: i32) {
+        let end = self.entries.partition_point(|(entry, _)| entry.x() < x);
+        let kept: Vec<(Rect, T)> = self
+            .entries
+            .drain(..end)
+            .filter(|(entry, _)| entry.right() > x)
+            .collect();
+        kept.into_iter()
+            .rev()
+            .for_each(|entry| self.entries.push_front(entry));
+    }
+}
+
+impl<T> Extend<(Rect, T)> for BroadPhase<T> {
+    fn extend<I: IntoIterator<Item = (Rect, T)>>(&mut self, entries: I) {
+        entries
+            .into_iter()
+            .for_each(|(bounds, item)| self.insert(bounds, item));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_entries_near_the_area_are_found() {
+        let mut broad_phase = BroadPhase::default();
+        broad_phase.extend((0..100).map(|index| {
+            (
+                Rect::new_from_x_y(index * 100, 500, 50, 50),
+                format!("stone {}", index),
+            )
+        }));
+        broad_phase.insert(Rect::new_from_x_y(150, 400, 1000, 20), "ledge".to_string());
+
+        let near = |x, y| -> Vec<&str> {
+            let area = Rect::new_from_x_y(x, y, 60, 120);
+            broad_phase.query(&area).map(String::as_str).collect()
+        };
+        assert_eq!(near(1020, 300), vec!["ledge"]);
+        assert_eq!(near(1020, 420), vec!["ledge", "stone 10"]);
+        assert_eq!(near(1160, 300), Vec::<&str>::new());
+
+        broad_phase.remove_left_of(1125);
+        assert_eq!(broad_phase.iter().count(), 90);
+        assert_eq!(broad_phase.iter().next().unwrap(), "ledge");
+    }
+}
@@ -14,3 +14,15 @@ pub fn add_click_handler(elem: HtmlElement) -> UnboundedReceiver<()> {
     on_click.forget();
     click_receiver
 }
+
+// Clicking the element taps `code`, so the click reaches the game as a key
+// and is recorded along with the rest of the input.
+pub fn add_key_handler(elem: HtmlElement, code: &'static str) {
+    let on_click = browser::closure_wrap(Box::new(move || {
+        if let Err(err) = browser::tap_key(code) {
+            error!("Error tapping {} {:#?}", code, err);
+        };
+    }) as Box<dyn FnMut()>);
+    elem.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+    on_click.forget();
+}
@@ -0,0 +1,47 @@
+use crate::engine::{Camera, Point, Renderer};
+
+impl Camera {
+    pub fn new(position: Point) -> Self {
+        Camera {
+            position,
+            previous_position: position,
+            zoom: 1.0,
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn move_to(&mut self, position: Point) {
+        self.previous_position = self.position;
+        self.position = position;
+    }
+
+    #[allow(dead_code)]
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+    }
+
+    // Where the camera sits between the last two ticks, blended by `alpha`.
+    pub fn interpolated_position(&self, alpha: f32) -> (f32, f32) {
+        let lerp =
+            |previous: i32, current: i32| previous as f32 + (current - previous) as f32 * alpha;
+        (
+            lerp(self.previous_position.x, self.position.x),
+            lerp(self.previous_position.y, self.position.y),
+        )
+    }
+
+    // Draws world-space content as seen from the camera.
+    pub fn draw(&self, renderer: &dyn Renderer, alpha: f32, draw: impl FnOnce()) {
+        let (x, y) = self.interpolated_position(alpha);
+        renderer.save();
+        if self.zoom != 1.0 {
+            renderer.scale(self.zoom, self.zoom);
+        }
+        renderer.translate(-x, -y);
+        draw();
+        renderer.restore();
+    }
+}
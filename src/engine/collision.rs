@@ -0,0 +1,169 @@
+use crate::engine::{Impact, Manifold, Normal, Point, Rect};
+
+impl Rect {
+    // Finds the shortest way to push this rectangle back out of `other`.
+    // Ties go to the top, so something standing on a row of boxes never
+    // catches on the seams between them.
+    pub fn manifold(&self, other: &Rect) -> Option<Manifold> {
+        if !self.intersects(other) {
+            return None;
+        }
+        [
+            (Normal::Top, self.bottom() - other.y()),
+            (Normal::Bottom, other.bottom() - self.y()),
+            (Normal::Left, self.right() - other.x()),
+            (Normal::Right, other.right() - self.x()),
+        ]
+        .into_iter()
+        .min_by_key(|(_, depth)| *depth)
+        .map(|(normal, depth)| Manifold { normal, depth })
+    }
+
+    // Moves this rectangle by `movement` and reports the first time it
+    // touches `other` on the way, even if it has passed right through by the
+    // end. The face is whichever one it crossed last, which the shortest
+    // way out can get wrong after a long move.
+    pub fn sweep(&self, movement: Point, other: &Rect) -> Option<Impact> {
+        let end = Rect::new_from_x_y(
+            self.x() + movement.x,
+            self.y() + movement.y,
+            self.width,
+            self.height,
+        );
+        let (x_entry, x_exit) = slab(self.x(), self.right(), movement.x, other.x(), other.right())?;
+        let (y_entry, y_exit) = slab(
+            self.y(),
+            self.bottom(),
+            movement.y,
+            other.y(),
+            other.bottom(),
+        )?;
+        let entry = x_entry.max(y_entry);
+        if entry >= x_exit.min(y_exit) || entry >= 1.0 {
+            return None;
+        }
+        if entry < 0.0 {
+            return end.manifold(other).map(|manifold| Impact {
+                time: 0.0,
+                manifold,
+            });
+        }
+        let normal = match (x_entry > y_entry, movement.x > 0, movement.y > 0) {
+            (true, true, _) => Normal::Left,
+            (true, false, _) => Normal::Right,
+            (false, _, true) => Normal::Top,
+            (false, _, false) => Normal::Bottom,
+        };
+        let depth = match normal {
+            Normal::Top => end.bottom() - other.y(),
+            Normal::Bottom => other.bottom() - end.y(),
+            Normal::Left => end.right() - other.x(),
+            Normal::Right => other.right() - end.x(),
+        };
+        Some(Impact {
+            time: entry,
+            manifold: Manifold { normal, depth },
+        })
+    }
+}
+
+// The fractions of a move along one axis at which the span from `start` to
+// `end` begins and stops overlapping the span from `low` to `high`.
+fn slab(start: i32, end: i32, movement: i32, low: i32, high: i32) -> Option<(f32, f32)> {
+    if movement == 0 {
+        return (start < high && end > low).then_some((f32::NEG_INFINITY, f32::INFINITY));
+    }
+    let (near, far) = if movement > 0 {
+        (low - end, high - start)
+    } else {
+        (high - start, low - end)
+    };
+    Some((near as f32 / movement as f32, far as f32 / movement as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_shallowest_face_is_the_one_that_was_hit() {
+        let platform = Rect::new_from_x_y(100, 100, 200, 50);
+        let contact = |x, y| Rect::new_from_x_y(x, y, 40, 40).manifold(&platform);
+
+        assert_eq!(contact(150, 0), None);
+        assert_eq!(
+            contact(150, 65),
+            Some(Manifold {
+                normal: Normal::Top,
+                depth: 5
+            })
+        );
+        assert_eq!(
+            contact(150, 140),
+            Some(Manifold {
+                normal: Normal::Bottom,
+                depth: 10
+            })
+        );
+        assert_eq!(
+            contact(64, 110),
+            Some(Manifold {
+                normal: Normal::Left,
+                depth: 4
+            })
+        );
+        assert_eq!(
+            contact(290, 110),
+            Some(Manifold {
+                normal: Normal::Right,
+                depth: 10
+            })
+        );
+        assert_eq!(
+            contact(62, 62),
+            Some(Manifold {
+                normal: Normal::Top,
+                depth: 2
+            })
+        );
+    }
+
+    #[test]
+    fn fast_moves_still_hit_what_they_pass_through() {
+        let ledge = Rect::new_from_x_y(100, 100, 200, 5);
+        let boy = Rect::new_from_x_y(150, 60, 20, 20);
+
+        assert_eq!(
+            boy.sweep(Point { x: 0, y: 40 }, &ledge),
+            Some(Impact {
+                time: 0.5,
+                manifold: Manifold {
+                    normal: Normal::Top,
+                    depth: 20
+                }
+            })
+        );
+        assert_eq!(boy.sweep(Point { x: 0, y: 15 }, &ledge), None);
+        assert_eq!(boy.sweep(Point { x: 300, y: 40 }, &ledge), None);
+
+        // Coming in low from the left, the side is crossed after the top
+        // even though the top is the shallower way out by the end.
+        let wall = Rect::new_from_x_y(100, 100, 200, 200);
+        assert_eq!(
+            Rect::new_from_x_y(90, 83, 20, 20)
+                .manifold(&wall)
+                .map(|manifold| manifold.normal),
+            Some(Normal::Top)
+        );
+        assert_eq!(
+            Rect::new_from_x_y(70, 79, 20, 20).sweep(Point { x: 20, y: 4 }, &wall),
+            Some(Impact {
+                time: 0.5,
+                manifold: Manifold {
+                    normal: Normal::Left,
+                    depth: 10
+                }
+            })
+        );
+    }
+}
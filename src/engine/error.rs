@@ -0,0 +1,183 @@
+use crate::browser;
+use crate::engine::{button, EngineError, ErrorScene, FailedAssets, Point, Rect, Renderer};
+use anyhow::{Error, Result};
+use futures::StreamExt;
+use std::fmt;
+
+const WIDTH: i32 = 600;
+const HEIGHT: i32 = 600;
+const MARGIN: i32 = 40;
+const LINE_HEIGHT: i32 = 24;
+const LINE_LENGTH: usize = 48;
+
+impl EngineError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EngineError::Fetch { .. } => "fetch",
+            EngineError::Decode { .. } => "decode",
+            EngineError::Parse { .. } => "parse",
+            EngineError::MissingFrame(_) => "missing-frame",
+            EngineError::Dom(_) => "dom",
+        }
+    }
+
+    pub fn find(err: &Error) -> Option<&EngineError> {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<EngineError>())
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Fetch { resource, message } => {
+                write!(f, "Could not fetch {}: {}", resource, message)
+            }
+            EngineError::Decode { resource, message } => {
+                write!(f, "Could not decode {}: {}", resource, message)
+            }
+            EngineError::Parse { resource, message } => {
+                write!(f, "Could not parse {}: {}", resource, message)
+            }
+            EngineError::MissingFrame(frame) => write!(f, "Sprite frame {} is missing", frame),
+            EngineError::Dom(message) => write!(f, "Page is missing something: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl fmt::Display for FailedAssets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of the assets did not load", self.0.len())
+    }
+}
+
+impl ErrorScene {
+    pub fn new(err: &Error) -> Self {
+        let title = match EngineError::find(err) {
+            Some(engine_error) => format!("Something went wrong ({})", engine_error.kind()),
+            None => "Something went wrong".to_string(),
+        };
+        ErrorScene {
+            title,
+            message: format!("{:#}", err),
+            failures: err
+                .downcast_ref::<FailedAssets>()
+                .map(|failed| failed.0.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        renderer.clear(&Rect::new_from_x_y(0, 0, WIDTH, HEIGHT));
+        let lines = std::iter::once(self.title.clone())
+            .chain(wrap(&self.message))
+            .chain(
+                self.failures
+                    .iter()
+                    .map(|path| format!("Could not load {}", path)),
+            );
+        for (line, text) in lines.enumerate() {
+            let location = Point {
+                x: MARGIN,
+                y: MARGIN + LINE_HEIGHT * (line as i32 + 1),
+            };
+            if let Err(err) = renderer.draw_text(&text, &location) {
+                error!("Could not draw error scene {:#?}", err);
+            }
+        }
+    }
+
+    pub async fn wait_for_retry(&self, renderer: &dyn Renderer) -> Result<()> {
+        self.draw(renderer);
+        browser::draw_ui("<button id='retry'>Retry</button>")?;
+        let mut retry = button::add_click_handler(browser::find_html_element_by_id("retry")?);
+        retry.next().await;
+        browser::hide_ui()
+    }
+}
+
+fn wrap(message: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for word in message.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + word.len() < LINE_LENGTH => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{DrawCommand, RecordingRenderer};
+    use anyhow::Context;
+
+    #[test]
+    fn error_scene_names_the_failure_and_wraps_the_message() {
+        let err = Err::<(), _>(EngineError::MissingFrame("Run (1).png".to_string()))
+            .context("Could not create the red hat boy from rhb_trimmed.json")
+            .unwrap_err();
+
+        let renderer = RecordingRenderer::new();
+        ErrorScene::new(&err).draw(&renderer);
+
+        let text: Vec<String> = renderer
+            .commands()
+            .into_iter()
+            .filter_map(|command| match command {
+                DrawCommand::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            text,
+            vec![
+                "Something went wrong (missing-frame)",
+                "Could not create the red hat boy from",
+                "rhb_trimmed.json: Sprite frame Run (1).png is",
+                "missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn error_scene_lists_the_assets_that_did_not_load() {
+        let err = Err::<(), _>(EngineError::Fetch {
+            resource: "die.wav".to_string(),
+            message: "404".to_string(),
+        })
+        .context(FailedAssets(vec![
+            "die.wav".to_string(),
+            "BG.png".to_string(),
+        ]))
+        .unwrap_err();
+
+        let renderer = RecordingRenderer::new();
+        ErrorScene::new(&err).draw(&renderer);
+
+        let text: Vec<String> = renderer
+            .commands()
+            .into_iter()
+            .filter_map(|command| match command {
+                DrawCommand::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            text,
+            vec![
+                "Something went wrong (fetch)",
+                "2 of the assets did not load: Could not fetch",
+                "die.wav: 404",
+                "Could not load die.wav",
+                "Could not load BG.png",
+            ]
+        );
+    }
+}
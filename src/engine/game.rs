@@ -1,58 +1,339 @@
 use crate::browser;
 use crate::engine::keys::{prepare_input, process_input};
-use crate::engine::{draw_frame_rate, GameLoop, KeyState, Renderer};
+use crate::engine::{
+    draw_frame_rate, CanvasRenderer, FailedAssets, GameLoop, GameLoopHandle, InputSource, KeyState,
+    LoadingScreen, LoopConfig, LoopStatus,
+};
 use crate::game::Game;
 
 use anyhow::{anyhow, Result};
-use std::cell::RefCell;
+use futures::channel::mpsc::unbounded;
+use futures::future::join;
+use futures::StreamExt;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
 
-const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
+// The rate everything the game moves each tick is tuned for.
+const DEFAULT_TICK_RATE: f32 = 60.0;
+const DEFAULT_MAX_CATCH_UP_TICKS: u32 = 10;
 
 type SharedLoopClosure = Rc<RefCell<Option<browser::LoopClosure>>>;
 
 impl GameLoop {
-    pub async fn start(game: impl Game + 'static) -> Result<()> {
-        let mut keyevent_receiver = prepare_input()?;
-
-        let mut game = game.initialize().await?;
-        let mut game_loop = GameLoop {
-            last_frame: browser::now()?,
-            accumulated_delta: 0.0,
-        };
-
-        let renderer = Renderer {
+    pub async fn start(
+        game: impl Game + 'static,
+        mut input: InputSource,
+        config: LoopConfig,
+    ) -> Result<GameLoopHandle> {
+        let renderer = CanvasRenderer {
             context: browser::context()?,
         };
+        let game = Rc::new(RefCell::new(show_loading(&game, &renderer).await?));
+        let (mut keyevent_receiver, key_listeners) = match input {
+            InputSource::Live(_) => {
+                let (receiver, listeners) = prepare_input()?;
+                (Some(receiver), Some(listeners))
+            }
+            InputSource::Replay(_) => (None, None),
+        };
+        let game_loop = Rc::new(RefCell::new(GameLoop {
+            last_frame: browser::now()?,
+            accumulated_delta: 0.0,
+            status: LoopStatus::Running,
+            auto_paused: false,
+            animation_frame: None,
+        }));
+        let interrupted = Rc::new(Cell::new(false));
+        let page_listeners = PageListeners::attach(interrupted.clone())?;
+        let frame_size = config.frame_size();
 
         let f: SharedLoopClosure = Rc::new(RefCell::new(None));
         let g = f.clone();
+        let shared_loop = game_loop.clone();
+        let shared_game = game.clone();
         let mut keystate = KeyState::new();
+        let mut game_paused = false;
         *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
-            process_input(&mut keystate, &mut keyevent_receiver);
+            let mut game_loop = shared_loop.borrow_mut();
+            game_loop.animation_frame = None;
+            if game_loop.status == LoopStatus::Stopped {
+                return;
+            }
+            let mut game = shared_game.borrow_mut();
+
+            if interrupted.replace(false)
+                && keyevent_receiver.is_some()
+                && game_loop.status == LoopStatus::Running
+            {
+                game_loop.status = LoopStatus::Paused;
+                game_loop.auto_paused = true;
+                // Key up events are lost while the page is in the background.
+                keystate = KeyState::new();
+            }
+
+            if let Some(keyevent_receiver) = keyevent_receiver.as_mut() {
+                process_input(&mut keystate, keyevent_receiver);
+            }
+            if game_loop.auto_paused && keystate.pressed().next().is_some() {
+                game_loop.status = LoopStatus::Running;
+                game_loop.auto_paused = false;
+            }
+
+            let paused = game_loop.status == LoopStatus::Paused;
+            if paused != game_paused {
+                game.set_paused(paused);
+                game_paused = paused;
+            }
+
             let frame_time = perf - game_loop.last_frame;
-            game_loop.accumulated_delta += frame_time as f32;
-            while game_loop.accumulated_delta > FRAME_SIZE {
+            let ticks = if paused {
+                0
+            } else {
+                game_loop.take_ticks(frame_time as f32, &config)
+            };
+            for _ in 0..ticks {
+                match &mut input {
+                    InputSource::Live(recording) => recording.borrow_mut().record(&keystate),
+                    InputSource::Replay(player) => {
+                        keystate = player.next_tick().unwrap_or_default()
+                    }
+                }
                 game.update(&keystate);
-                game_loop.accumulated_delta -= FRAME_SIZE;
+                keystate.end_tick();
             }
             game_loop.last_frame = perf;
 
-            game.draw(&renderer);
+            game.draw(&renderer, game_loop.accumulated_delta / frame_size);
 
             if cfg!(debug_assertions) {
                 unsafe { draw_frame_rate(&renderer, frame_time) }
             }
 
-            if let Err(err) = browser::request_animation_frame(f.borrow().as_ref().unwrap()) {
-                panic!("Error while requesting animation frame {:#?}", err);
+            if let Some(closure) = f.borrow().as_ref() {
+                match browser::request_animation_frame(closure) {
+                    Ok(handle) => game_loop.animation_frame = Some(handle),
+                    Err(err) => {
+                        panic!("Error while requesting animation frame {:#?}", err);
+                    }
+                }
             }
         }));
-        browser::request_animation_frame(
+        game_loop.borrow_mut().animation_frame = Some(browser::request_animation_frame(
             g.borrow()
                 .as_ref()
                 .ok_or_else(|| anyhow!("GameLoop: Loop is None"))?,
-        )?;
+        )?);
+
+        Ok(GameLoopHandle {
+            game_loop,
+            game,
+            closure: g,
+            key_listeners,
+            page_listeners,
+        })
+    }
+
+    // Adds the time since the last frame and takes out as many whole ticks as
+    // it covers, leaving the rest to blend the next draw with.
+    fn take_ticks(&mut self, frame_time: f32, config: &LoopConfig) -> u32 {
+        let frame_size = config.frame_size();
+        let max_accumulated_delta = config.max_catch_up_ticks as f32 * frame_size;
+        self.accumulated_delta = (self.accumulated_delta + frame_time).min(max_accumulated_delta);
+        let mut ticks = 0;
+        while self.accumulated_delta > frame_size {
+            self.accumulated_delta -= frame_size;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    fn cancel_animation_frame(&mut self) -> Result<()> {
+        match self.animation_frame.take() {
+            Some(handle) => browser::cancel_animation_frame(handle),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn show_loading(game: &impl Game, renderer: &CanvasRenderer) -> Result<Box<dyn Game>> {
+    let (progress, events) = unbounded();
+    let mut screen = LoadingScreen::new();
+    screen.draw(renderer);
+    // The event stream ends once initialize drops its sender, so both
+    // futures finish together.
+    let (game, _) = join(
+        game.initialize(progress),
+        events.for_each(|event| {
+            screen.apply(event);
+            screen.draw(renderer);
+            futures::future::ready(())
+        }),
+    )
+    .await;
+    game.map_err(|err| {
+        if screen.failures.is_empty() {
+            err
+        } else {
+            err.context(FailedAssets(screen.failures))
+        }
+    })
+}
+
+impl GameLoopHandle {
+    pub fn status(&self) -> LoopStatus {
+        self.game_loop.borrow().status
+    }
+
+    pub fn pause(&self) {
+        let mut game_loop = self.game_loop.borrow_mut();
+        if game_loop.status == LoopStatus::Running {
+            game_loop.status = LoopStatus::Paused;
+        }
+        game_loop.auto_paused = false;
+    }
+
+    pub fn resume(&self) {
+        let mut game_loop = self.game_loop.borrow_mut();
+        if game_loop.status == LoopStatus::Paused {
+            game_loop.status = LoopStatus::Running;
+            game_loop.auto_paused = false;
+        }
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        {
+            let mut game_loop = self.game_loop.borrow_mut();
+            game_loop.status = LoopStatus::Stopped;
+            game_loop.cancel_animation_frame()?;
+        }
+        // The loop closure keeps a reference to its own slot in order to
+        // schedule the next frame, so it has to be dropped explicitly.
+        self.closure.borrow_mut().take();
+        self.game.borrow_mut().shutdown();
+        self.page_listeners.detach()?;
+        match self.key_listeners.take() {
+            Some(key_listeners) => key_listeners.detach(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl LoopConfig {
+    fn frame_size(&self) -> f32 {
+        1.0 / self.tick_rate * 1000.0
+    }
+
+    // How many ticks at the default rate one tick stands for, which is what
+    // the game scales its movement by.
+    pub fn tick_scale(&self) -> f32 {
+        DEFAULT_TICK_RATE / self.tick_rate
+    }
+}
+
+impl Default for LoopConfig {
+    fn default() -> Self {
+        LoopConfig {
+            tick_rate: DEFAULT_TICK_RATE,
+            max_catch_up_ticks: DEFAULT_MAX_CATCH_UP_TICKS,
+        }
+    }
+}
+
+pub struct PageListeners {
+    _onvisibilitychange: Closure<dyn FnMut()>,
+    _onblur: Closure<dyn FnMut()>,
+}
+
+impl PageListeners {
+    fn attach(interrupted: Rc<Cell<bool>>) -> Result<Self> {
+        let document = browser::document()?;
+        let hidden_document = document.clone();
+        let visibility_interrupted = interrupted.clone();
+        let onvisibilitychange = browser::closure_wrap(Box::new(move || {
+            if hidden_document.hidden() {
+                visibility_interrupted.set(true);
+            }
+        }) as Box<dyn FnMut()>);
+        let onblur = browser::closure_wrap(Box::new(move || {
+            interrupted.set(true);
+        }) as Box<dyn FnMut()>);
+
+        document.set_onvisibilitychange(Some(onvisibilitychange.as_ref().unchecked_ref()));
+        browser::window()?.set_onblur(Some(onblur.as_ref().unchecked_ref()));
+
+        Ok(PageListeners {
+            _onvisibilitychange: onvisibilitychange,
+            _onblur: onblur,
+        })
+    }
+
+    fn detach(&self) -> Result<()> {
+        browser::document()?.set_onvisibilitychange(None);
+        browser::window()?.set_onblur(None);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pixels a tick at the default tick rate, as fast as the boy runs when a
+    // game starts.
+    const RUNNING_SPEED: f32 = 4.0;
+
+    // How far the boy is drawn after running for `seconds` on a display that
+    // refreshes `refresh_rate` times a second, ticking `tick_rate` times.
+    fn distance_drawn(refresh_rate: f32, tick_rate: f32, seconds: f32) -> f32 {
+        let mut game_loop = GameLoop {
+            last_frame: 0.0,
+            accumulated_delta: 0.0,
+            status: LoopStatus::Running,
+            auto_paused: false,
+            animation_frame: None,
+        };
+        let config = LoopConfig {
+            tick_rate,
+            ..LoopConfig::default()
+        };
+        let frame_time = 1000.0 / refresh_rate;
+        let ticks: u32 = (0..(seconds * refresh_rate) as u32)
+            .map(|_| game_loop.take_ticks(frame_time, &config))
+            .sum();
+        (ticks as f32 + game_loop.accumulated_delta / config.frame_size())
+            * RUNNING_SPEED
+            * config.tick_scale()
+    }
+
+    #[test]
+    fn the_same_time_covers_the_same_distance_at_any_refresh_rate() {
+        let distance = distance_drawn(60.0, 60.0, 10.0);
+        assert!((distance - 2400.0).abs() < 0.5, "{}", distance);
+        for refresh_rate in [30.0, 120.0, 144.0] {
+            assert!(
+                (distance_drawn(refresh_rate, 60.0, 10.0) - distance).abs() < 0.5,
+                "{} at {}",
+                distance_drawn(refresh_rate, 60.0, 10.0),
+                refresh_rate
+            );
+        }
+    }
+
+    #[test]
+    fn the_same_time_covers_the_same_distance_at_any_tick_rate() {
+        for tick_rate in [30.0, 120.0, 144.0] {
+            for refresh_rate in [60.0, 144.0] {
+                let distance = distance_drawn(refresh_rate, tick_rate, 10.0);
+                assert!(
+                    (distance - 2400.0).abs() < 0.5,
+                    "{} ticking at {} on a {} Hz display",
+                    distance,
+                    tick_rate,
+                    refresh_rate
+                );
+            }
+        }
+    }
+}
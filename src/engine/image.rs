@@ -1,7 +1,7 @@
 use crate::browser;
 use crate::engine::Renderer;
-use crate::engine::{Image, Point, Rect};
-use anyhow::{anyhow, Result};
+use crate::engine::{EngineError, Image, ImageElement, Point, Rect};
+use anyhow::Result;
 use futures::channel::oneshot::channel;
 use std::rc::Rc;
 use std::sync::Mutex;
@@ -9,7 +9,7 @@ use wasm_bindgen::closure::Closure;
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::HtmlImageElement;
 
-pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
+pub async fn load_image(source: &str) -> Result<ImageElement> {
     let image = browser::new_image()?;
     let (complete_tx, complete_rx) = channel::<Result<()>>();
     let success_tx = Rc::new(Mutex::new(Some(complete_tx)));
@@ -21,9 +21,14 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
             };
         }
     });
+    let resource = source.to_string();
     let error_callback: Closure<dyn FnMut(JsValue)> = browser::closure_once(move |err| {
         if let Some(error_tx) = error_tx.lock().ok().and_then(|mut opt| opt.take()) {
-            if let Err(err) = error_tx.send(Err(anyhow!("Error Loading Image {:#?}", err))) {
+            let err = EngineError::Decode {
+                resource,
+                message: format!("{:#?}", err),
+            };
+            if let Err(err) = error_tx.send(Err(err.into())) {
                 error!("Error sending ok result in error_callback {:#?}", err);
             };
         }
@@ -32,35 +37,72 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
     image.set_onerror(Some(error_callback.as_ref().unchecked_ref()));
     image.set_src(source);
     complete_rx.await??;
-    Ok(image)
+    Ok(ImageElement::Html(image))
+}
+
+impl ImageElement {
+    #[cfg(test)]
+    pub fn headless(name: &str, width: u32, height: u32) -> Self {
+        ImageElement::Headless {
+            name: name.to_string(),
+            width,
+            height,
+        }
+    }
+
+    pub fn html(&self) -> Option<&HtmlImageElement> {
+        match self {
+            ImageElement::Html(element) => Some(element),
+            #[cfg(test)]
+            ImageElement::Headless { .. } => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn name(&self) -> String {
+        match self {
+            ImageElement::Html(element) => element.src(),
+            ImageElement::Headless { name, .. } => name.clone(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        match self {
+            ImageElement::Html(element) => element.width(),
+            #[cfg(test)]
+            ImageElement::Headless { width, .. } => *width,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            ImageElement::Html(element) => element.height(),
+            #[cfg(test)]
+            ImageElement::Headless { height, .. } => *height,
+        }
+    }
+}
+
+impl From<HtmlImageElement> for ImageElement {
+    fn from(element: HtmlImageElement) -> Self {
+        ImageElement::Html(element)
+    }
 }
 
 impl Image {
-    pub fn new(element: HtmlImageElement, position: Point) -> Self {
-        let bounding_box = Rect::new(position, element.width() as i16, element.height() as i16);
+    pub fn new(element: ImageElement, position: Point) -> Self {
+        let bounding_box = Rect::new(position, element.width() as i32, element.height() as i32);
         Self {
             element,
             bounding_box,
         }
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
+    pub fn draw(&self, renderer: &dyn Renderer) {
         renderer.draw_entire_image(&self.element, &self.bounding_box.position);
     }
 
     pub fn bounding_box(&self) -> &Rect {
         &self.bounding_box
     }
-
-    pub fn move_horizontally(&mut self, distance: i16) {
-        self.set_x(self.bounding_box.x() + distance);
-    }
-
-    pub fn set_x(&mut self, x: i16) {
-        self.bounding_box.set_x(x);
-    }
-
-    pub fn right(&self) -> i16 {
-        self.bounding_box.right()
-    }
 }
@@ -3,8 +3,8 @@ use crate::engine::KeyState;
 use anyhow::Result;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 
 pub enum KeyPress {
@@ -12,7 +12,21 @@ pub enum KeyPress {
     KeyDown(web_sys::KeyboardEvent),
 }
 
-pub fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
+pub struct KeyListeners {
+    _onkeydown: Closure<dyn FnMut(web_sys::KeyboardEvent)>,
+    _onkeyup: Closure<dyn FnMut(web_sys::KeyboardEvent)>,
+}
+
+impl KeyListeners {
+    pub fn detach(self) -> Result<()> {
+        let window = browser::window()?;
+        window.set_onkeydown(None);
+        window.set_onkeyup(None);
+        Ok(())
+    }
+}
+
+pub fn prepare_input() -> Result<(UnboundedReceiver<KeyPress>, KeyListeners)> {
     let (keydown_sender, keyevent_receiver) = unbounded();
     let keydown_sender = Rc::new(RefCell::new(keydown_sender));
     let keyup_sender = Rc::clone(&keydown_sender);
@@ -38,29 +52,50 @@ pub fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
     browser::window()?.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
     browser::window()?.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
 
-    onkeydown.forget();
-    onkeyup.forget();
-
-    Ok(keyevent_receiver)
+    Ok((
+        keyevent_receiver,
+        KeyListeners {
+            _onkeydown: onkeydown,
+            _onkeyup: onkeyup,
+        },
+    ))
 }
 
 impl KeyState {
     pub fn new() -> Self {
-        KeyState {
-            pressed_keys: HashMap::new(),
-        }
+        KeyState::default()
     }
 
     pub fn is_pressed(&self, code: &str) -> bool {
-        self.pressed_keys.contains_key(code)
+        self.pressed_keys.contains(code)
     }
 
-    fn set_pressed(&mut self, code: &str, event: web_sys::KeyboardEvent) {
-        self.pressed_keys.insert(code.into(), event);
+    pub fn pressed(&self) -> impl Iterator<Item = &String> {
+        self.pressed_keys.iter()
     }
 
-    fn set_released(&mut self, code: &str) {
-        self.pressed_keys.remove(code);
+    pub fn set_pressed(&mut self, code: &str) {
+        if self.pressed_keys.insert(code.into()) {
+            self.unseen_keys.insert(code.into());
+        }
+        self.early_releases.remove(code);
+    }
+
+    pub fn set_released(&mut self, code: &str) {
+        if self.unseen_keys.contains(code) {
+            self.early_releases.insert(code.into());
+        } else {
+            self.pressed_keys.remove(code);
+        }
+    }
+
+    // Called once a tick has seen the keys, to let go of any that were only
+    // tapped.
+    pub fn end_tick(&mut self) {
+        self.unseen_keys.clear();
+        for code in self.early_releases.drain() {
+            self.pressed_keys.remove(&code);
+        }
     }
 }
 
@@ -71,7 +106,7 @@ pub fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedRece
             Err(_err) => break,
             Ok(Some(evt)) => match evt {
                 KeyPress::KeyUp(evt) => state.set_released(&evt.code()),
-                KeyPress::KeyDown(evt) => state.set_pressed(&evt.code(), evt),
+                KeyPress::KeyDown(evt) => state.set_pressed(&evt.code()),
             },
         };
     }
@@ -0,0 +1,88 @@
+use crate::engine::{AssetEvent, LoadingScreen, Point, Rect, Renderer};
+
+const WIDTH: i32 = 600;
+const HEIGHT: i32 = 600;
+const BAR: Rect = Rect {
+    position: Point { x: 100, y: 280 },
+    width: 400,
+    height: 20,
+};
+const BAR_COLOR: &str = "#FF5000";
+const FAILURE_LINE_HEIGHT: i32 = 24;
+
+impl LoadingScreen {
+    pub fn new() -> Self {
+        LoadingScreen::default()
+    }
+
+    pub fn apply(&mut self, event: AssetEvent) {
+        match event {
+            AssetEvent::Queued(count) => self.total += count,
+            AssetEvent::Loaded => self.loaded += 1,
+            AssetEvent::Failed(path) => self.failures.push(path),
+        }
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        renderer.clear(&Rect::new_from_x_y(0, 0, WIDTH, HEIGHT));
+        let finished = self.loaded + self.failures.len();
+        let text = format!("Loading {} / {}", finished, self.total);
+        if let Err(err) = renderer.draw_text(&text, &Point { x: 220, y: 260 }) {
+            error!("Could not draw loading progress {:#?}", err);
+        }
+
+        renderer.draw_rect(&BAR);
+        if let Some(width) = (BAR.width as usize * self.loaded).checked_div(self.total) {
+            renderer.fill_rect(
+                &Rect::new(BAR.position, width as i32, BAR.height),
+                BAR_COLOR,
+            );
+        }
+
+        self.failures.iter().enumerate().for_each(|(line, path)| {
+            let location = Point {
+                x: BAR.x(),
+                y: BAR.bottom() + FAILURE_LINE_HEIGHT * (line as i32 + 2),
+            };
+            if let Err(err) = renderer.draw_text(&format!("Could not load {}", path), &location) {
+                error!("Could not draw loading failure {:#?}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{DrawCommand, RecordingRenderer};
+
+    #[test]
+    fn draws_progress_and_lists_failed_assets() {
+        let mut screen = LoadingScreen::new();
+        screen.apply(AssetEvent::Queued(4));
+        screen.apply(AssetEvent::Loaded);
+        screen.apply(AssetEvent::Failed("die.wav".to_string()));
+
+        let renderer = RecordingRenderer::new();
+        screen.draw(&renderer);
+
+        assert_eq!(
+            renderer.commands()[1..],
+            [
+                DrawCommand::Text {
+                    text: "Loading 2 / 4".to_string(),
+                    location: Point { x: 220, y: 260 },
+                },
+                DrawCommand::Rect(BAR),
+                DrawCommand::FillRect {
+                    rect: Rect::new_from_x_y(100, 280, 100, 20),
+                    color: BAR_COLOR.to_string(),
+                },
+                DrawCommand::Text {
+                    text: "Could not load die.wav".to_string(),
+                    location: Point { x: 100, y: 348 },
+                },
+            ]
+        );
+    }
+}
@@ -1,73 +1,425 @@
+pub mod animation;
+pub mod assets;
 pub mod audio;
+pub mod broad_phase;
 pub mod button;
+pub mod camera;
+pub mod collision;
+pub mod error;
 pub mod game;
 pub mod image;
 pub mod keys;
+pub mod loading;
+pub mod parallax;
 pub mod renderer;
+pub mod replay;
 pub mod sprites;
+pub mod tiled;
 
-use crate::engine::sprites::SheetRect;
+use crate::browser::LoopClosure;
+use crate::engine::game::PageListeners;
+use crate::engine::keys::KeyListeners;
+use crate::engine::sprites::{SheetMeta, SheetRect, SheetSize};
+use crate::game::Game;
+use anyhow::Result;
 use serde::Deserialize;
-use std::collections::HashMap;
-use web_sys::{AudioBuffer, AudioContext, CanvasRenderingContext2d, HtmlImageElement};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    AudioBuffer, AudioBufferSourceNode, AudioContext, CanvasRenderingContext2d, HtmlImageElement,
+};
+
+#[derive(Debug)]
+pub enum EngineError {
+    Fetch { resource: String, message: String },
+    Decode { resource: String, message: String },
+    Parse { resource: String, message: String },
+    MissingFrame(String),
+    Dom(String),
+}
+
+// The assets the loading screen saw fail, attached to the error that kept
+// the game from starting so the error screen can still name them.
+#[derive(Debug)]
+pub struct FailedAssets(pub Vec<String>);
+
+pub struct ErrorScene {
+    title: String,
+    message: String,
+    failures: Vec<String>,
+}
+
+pub struct Camera {
+    position: Point,
+    previous_position: Point,
+    zoom: f32,
+}
+
+pub struct ParallaxLayer {
+    image: ImageElement,
+    scroll_factor: f32,
+    offset_y: i32,
+    tiled: bool,
+}
+
+pub struct ParallaxBackground {
+    layers: Vec<ParallaxLayer>,
+    width: i32,
+}
+
+// The background's layers as the game's data lists them, back to front,
+// each naming its image by its key in the manifest.
+#[derive(Deserialize)]
+pub struct ParallaxFile {
+    layers: Vec<LayerAsset>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerAsset {
+    pub image: String,
+    pub scroll_factor: f32,
+    #[serde(default)]
+    pub offset_y: i32,
+    #[serde(default)]
+    pub tiled: bool,
+}
 
 pub struct GameLoop {
     last_frame: f64,
     accumulated_delta: f32,
+    status: LoopStatus,
+    auto_paused: bool,
+    animation_frame: Option<i32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+pub struct LoopConfig {
+    pub tick_rate: f32,
+    pub max_catch_up_ticks: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopStatus {
+    Running,
+    Paused,
+    Stopped,
+}
+
+pub struct GameLoopHandle {
+    game_loop: Rc<RefCell<GameLoop>>,
+    game: Rc<RefCell<Box<dyn Game>>>,
+    closure: Rc<RefCell<Option<LoopClosure>>>,
+    key_listeners: Option<KeyListeners>,
+    page_listeners: PageListeners,
+}
+
+#[derive(Debug, Default)]
 pub struct KeyState {
-    pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+    pressed_keys: HashSet<String>,
+    // Keys pressed since the last tick, and those of them already let go of,
+    // which stay down for one tick so that no tick misses a quick tap.
+    unseen_keys: HashSet<String>,
+    early_releases: HashSet<String>,
+}
+
+#[derive(Clone)]
+pub enum InputSource {
+    Live(Rc<RefCell<Replay>>),
+    Replay(ReplayPlayer),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Seed {
+    pub value: u64,
+    pub fixed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    seed: Seed,
+    keys: Vec<String>,
+    runs: Vec<(u32, u32)>,
+}
+
+#[derive(Clone)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    run: usize,
+    tick: u32,
+}
+
+#[derive(Clone)]
+pub enum ImageElement {
+    Html(HtmlImageElement),
+    #[cfg(test)]
+    Headless {
+        name: String,
+        width: u32,
+        height: u32,
+    },
 }
 
 pub struct Image {
-    element: HtmlImageElement,
+    element: ImageElement,
     bounding_box: Rect,
 }
 
-pub struct Renderer {
+pub trait Renderer {
+    fn clear(&self, rect: &Rect);
+    fn draw_image(&self, image: &ImageElement, frame: &Rect, destination: &Rect);
+    fn draw_entire_image(&self, image: &ImageElement, position: &Point);
+    fn draw_rect(&self, rect: &Rect);
+    fn fill_rect(&self, rect: &Rect, color: &str);
+    fn draw_text(&self, text: &str, location: &Point) -> Result<()>;
+    fn save(&self);
+    fn restore(&self);
+    fn translate(&self, x: f32, y: f32);
+    fn rotate(&self, angle: f32);
+    fn scale(&self, x: f32, y: f32);
+    fn set_alpha(&self, alpha: f32);
+    fn set_composite(&self, operation: &str);
+
+    // `bounds` is the box the transform pivots around, which for a trimmed
+    // sprite is the whole untrimmed sprite rather than what gets drawn.
+    fn draw_image_with(
+        &self,
+        image: &ImageElement,
+        frame: &Rect,
+        destination: &Rect,
+        bounds: &Rect,
+        params: &DrawParams,
+    ) {
+        if params.is_identity() {
+            self.draw_image(image, frame, destination);
+        } else {
+            self.save();
+            params.apply(self, bounds);
+            self.draw_image(image, frame, destination);
+            self.restore();
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawParams {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub scale: f32,
+    pub rotation: f32,
+    pub pivot: (f32, f32),
+    pub alpha: f32,
+    pub composite: Option<String>,
+}
+
+pub struct CanvasRenderer {
     pub context: CanvasRenderingContext2d,
 }
 
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingRenderer {
+    commands: RefCell<Vec<DrawCommand>>,
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    Clear(Rect),
+    Image {
+        image: String,
+        frame: Rect,
+        destination: Rect,
+    },
+    EntireImage {
+        image: String,
+        position: Point,
+    },
+    Rect(Rect),
+    FillRect {
+        rect: Rect,
+        color: String,
+    },
+    Text {
+        text: String,
+        location: Point,
+    },
+    Save,
+    Restore,
+    Translate {
+        x: f32,
+        y: f32,
+    },
+    Rotate(f32),
+    Scale {
+        x: f32,
+        y: f32,
+    },
+    Alpha(f32),
+    Composite(String),
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlayMode {
+    #[default]
+    Loop,
+    Once,
+    PingPong,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    pub frames: Vec<String>,
+    pub frame_duration: u32,
+    #[serde(default)]
+    pub mode: PlayMode,
+    #[serde(default)]
+    pub events: Vec<FrameEvent>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(transparent)]
+pub struct Animations {
+    clips: HashMap<String, Clip>,
+}
+
+#[derive(Clone)]
+pub struct Animator {
+    animations: Rc<Animations>,
+    tick: u32,
+    carry: f32,
+    events: Vec<String>,
+}
+
 pub struct SpriteSheet {
     sheet: Sheet,
-    image: HtmlImageElement,
+    image: ImageElement,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetManifest {
+    #[serde(default)]
+    pub images: HashMap<String, String>,
+    #[serde(default)]
+    pub sprite_sheets: HashMap<String, SpriteSheetAsset>,
+    #[serde(default)]
+    pub sounds: HashMap<String, String>,
+    #[serde(default)]
+    pub json: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+pub struct SpriteSheetAsset {
+    pub json: String,
+    pub image: String,
+}
+
+pub enum AssetEvent {
+    Queued(usize),
+    Loaded,
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct LoadingScreen {
+    total: usize,
+    loaded: usize,
+    failures: Vec<String>,
+}
+
+pub struct Assets {
+    images: HashMap<String, ImageElement>,
+    sprite_sheets: HashMap<String, Rc<SpriteSheet>>,
+    sounds: HashMap<String, Sound>,
+    json: HashMap<String, JsValue>,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct Sheet {
+    #[serde(deserialize_with = "sprites::frames")]
     pub frames: HashMap<String, Cell>,
+    #[serde(default)]
+    pub meta: Option<SheetMeta>,
 }
 
 #[derive(Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Cell {
     pub frame: SheetRect,
+    #[serde(default)]
+    pub rotated: bool,
+    #[serde(default)]
+    pub trimmed: bool,
     pub sprite_source_size: SheetRect,
+    #[serde(default)]
+    pub source_size: Option<SheetSize>,
 }
 
-#[derive(Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Rect {
     pub position: Point,
-    pub width: i16,
-    pub height: i16,
+    pub width: i32,
+    pub height: i32,
+}
+
+// Things laid out along the track, kept in order of their left edges so
+// the few near a point can be found without looking at the rest.
+pub struct BroadPhase<T> {
+    entries: VecDeque<(Rect, T)>,
+    widest: i32,
+}
+
+// The face of the rectangle that was run into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normal {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+// How far one rectangle has sunk into another, and through which face.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Manifold {
+    pub normal: Normal,
+    pub depth: i32,
+}
+
+// Where along a move one rectangle first touches another, as a fraction of
+// the move, and how far in it ends up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Impact {
+    pub time: f32,
+    pub manifold: Manifold,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Point {
-    pub x: i16,
-    pub y: i16,
+    pub x: i32,
+    pub y: i32,
 }
 
 #[derive(Clone)]
 pub struct Audio {
-    context: AudioContext,
+    context: Option<AudioContext>,
+    // Looping sounds never end by themselves, so they are kept to be stopped.
+    looping: Rc<RefCell<Vec<AudioBufferSourceNode>>>,
 }
 
 #[derive(Clone)]
 pub struct Sound {
-    pub buffer: AudioBuffer,
+    pub buffer: Option<AudioBuffer>,
 }
 
 #[cfg(test)]
@@ -90,7 +442,7 @@ mod tests {
     }
 }
 
-unsafe fn draw_frame_rate(renderer: &Renderer, frame_time: f64) {
+unsafe fn draw_frame_rate(renderer: &dyn Renderer, frame_time: f64) {
     static mut FRAMES_COUNTED: i32 = 0;
     static mut TOTAL_FRAME_TIME: f64 = 0.0;
     static mut FRAME_RATE: i32 = 0;
@@ -0,0 +1,129 @@
+use crate::engine::{
+    Assets, ImageElement, ParallaxBackground, ParallaxFile, ParallaxLayer, Point, Renderer,
+};
+use anyhow::{anyhow, Result};
+
+impl ParallaxLayer {
+    pub fn new(image: ImageElement, scroll_factor: f32, offset_y: i32, tiled: bool) -> Self {
+        ParallaxLayer {
+            image,
+            scroll_factor,
+            offset_y,
+            tiled,
+        }
+    }
+
+    fn draw(&self, renderer: &dyn Renderer, camera_x: f32, width: i32) {
+        let scrolled = (camera_x * self.scroll_factor).round() as i32;
+        let image_width = self.image.width() as i32;
+        if !self.tiled || image_width == 0 {
+            renderer.draw_entire_image(
+                &self.image,
+                &Point {
+                    x: -scrolled,
+                    y: self.offset_y,
+                },
+            );
+            return;
+        }
+        // A tiled layer repeats from wherever its scroll lands inside one
+        // image width, so it wraps without tracking any copies of itself.
+        let mut x = -scrolled.rem_euclid(image_width);
+        while x < width {
+            renderer.draw_entire_image(
+                &self.image,
+                &Point {
+                    x,
+                    y: self.offset_y,
+                },
+            );
+            x += image_width;
+        }
+    }
+}
+
+impl ParallaxFile {
+    pub fn check(&self) -> Result<()> {
+        if self.layers.is_empty() {
+            return Err(anyhow!("The background needs at least one layer"));
+        }
+        Ok(())
+    }
+
+    pub fn layers(&self, assets: &Assets) -> Result<Vec<ParallaxLayer>> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                Ok(ParallaxLayer::new(
+                    assets.image(&layer.image)?,
+                    layer.scroll_factor,
+                    layer.offset_y,
+                    layer.tiled,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl ParallaxBackground {
+    pub fn new(layers: Vec<ParallaxLayer>, width: i32) -> Self {
+        ParallaxBackground { layers, width }
+    }
+
+    // Layers are drawn in screen space back to front, each scrolled by its
+    // share of the camera's position.
+    pub fn draw(&self, renderer: &dyn Renderer, camera_x: f32) {
+        self.layers
+            .iter()
+            .for_each(|layer| layer.draw(renderer, camera_x, self.width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{DrawCommand, RecordingRenderer};
+
+    #[test]
+    fn layers_scroll_by_their_factor_and_tiles_wrap() {
+        let background = ParallaxBackground::new(
+            vec![
+                ParallaxLayer::new(ImageElement::headless("sky.png", 800, 600), 0.0, 0, false),
+                ParallaxLayer::new(
+                    ImageElement::headless("hills.png", 400, 200),
+                    0.5,
+                    300,
+                    true,
+                ),
+            ],
+            600,
+        );
+
+        let renderer = RecordingRenderer::new();
+        background.draw(&renderer, 1000.0);
+
+        let drawn = |image: &str, x, y| DrawCommand::EntireImage {
+            image: image.to_string(),
+            position: Point { x, y },
+        };
+        assert_eq!(
+            renderer.commands(),
+            vec![
+                drawn("sky.png", 0, 0),
+                drawn("hills.png", -100, 300),
+                drawn("hills.png", 300, 300),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_layers_come_from_the_game_data() {
+        let parallax: ParallaxFile =
+            serde_json::from_str(include_str!("../../static/parallax.json")).unwrap();
+        assert!(parallax.check().is_ok());
+        assert_eq!(parallax.layers[0].image, "background");
+
+        let empty: ParallaxFile = serde_json::from_str(r#"{"layers": []}"#).unwrap();
+        assert!(empty.check().is_err());
+    }
+}
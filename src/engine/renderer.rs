@@ -1,10 +1,11 @@
-use crate::engine::{Point, Rect, Renderer};
+use crate::engine::{CanvasRenderer, DrawParams, ImageElement, Point, Rect, Renderer};
+#[cfg(test)]
+use crate::engine::{DrawCommand, RecordingRenderer};
 use anyhow::{anyhow, Result};
 use wasm_bindgen::JsValue;
-use web_sys::HtmlImageElement;
 
-impl Renderer {
-    pub fn clear(&self, rect: &Rect) {
+impl Renderer for CanvasRenderer {
+    fn clear(&self, rect: &Rect) {
         self.context.clear_rect(
             rect.x().into(),
             rect.y().into(),
@@ -13,30 +14,33 @@ impl Renderer {
         )
     }
 
-    pub fn draw_image(&self, image: &HtmlImageElement, frame: &Rect, destination: &Rect) {
-        self.context
-            .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                image,
-                frame.x().into(),
-                frame.y().into(),
-                frame.width.into(),
-                frame.height.into(),
-                destination.x().into(),
-                destination.y().into(),
-                destination.width.into(),
-                destination.height.into(),
-            )
-            .expect("Drawing is launching unrecoverable errors");
-    }
-
-    pub fn draw_entire_image(&self, image: &HtmlImageElement, position: &Point) {
-        self.context
-            .draw_image_with_html_image_element(image, position.x.into(), position.y.into())
-            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+    fn draw_image(&self, image: &ImageElement, frame: &Rect, destination: &Rect) {
+        if let Some(image) = image.html() {
+            self.context
+                .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                    image,
+                    frame.x().into(),
+                    frame.y().into(),
+                    frame.width.into(),
+                    frame.height.into(),
+                    destination.x().into(),
+                    destination.y().into(),
+                    destination.width.into(),
+                    destination.height.into(),
+                )
+                .expect("Drawing is launching unrecoverable errors");
+        }
+    }
+
+    fn draw_entire_image(&self, image: &ImageElement, position: &Point) {
+        if let Some(image) = image.html() {
+            self.context
+                .draw_image_with_html_image_element(image, position.x.into(), position.y.into())
+                .expect("Drawing is throwing exceptions! Unrecoverable error.");
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn draw_rect(&self, rect: &Rect) {
+    fn draw_rect(&self, rect: &Rect) {
         self.context.set_stroke_style(&JsValue::from_str("#FF5000"));
         self.context.begin_path();
         self.context.rect(
@@ -48,12 +52,250 @@ impl Renderer {
         self.context.stroke();
     }
 
-    #[allow(dead_code)]
-    pub fn draw_text(&self, text: &str, location: &Point) -> Result<()> {
+    fn fill_rect(&self, rect: &Rect, color: &str) {
+        self.context.save();
+        self.context.set_fill_style(&JsValue::from_str(color));
+        self.context.fill_rect(
+            rect.x().into(),
+            rect.y().into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+        self.context.restore();
+    }
+
+    fn draw_text(&self, text: &str, location: &Point) -> Result<()> {
         self.context.set_font("16pt serif");
         self.context
             .fill_text(text, location.x.into(), location.y.into())
             .map_err(|err| anyhow!("Error filling text {:#?}", err))?;
         Ok(())
     }
+
+    fn save(&self) {
+        self.context.save();
+    }
+
+    fn restore(&self) {
+        self.context.restore();
+    }
+
+    fn translate(&self, x: f32, y: f32) {
+        self.context
+            .translate(x.into(), y.into())
+            .expect("Translating is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn rotate(&self, angle: f32) {
+        self.context
+            .rotate(angle.into())
+            .expect("Rotating is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn scale(&self, x: f32, y: f32) {
+        self.context
+            .scale(x.into(), y.into())
+            .expect("Scaling is throwing exceptions! Unrecoverable error.");
+    }
+
+    fn set_alpha(&self, alpha: f32) {
+        self.context.set_global_alpha(alpha.into());
+    }
+
+    fn set_composite(&self, operation: &str) {
+        if let Err(err) = self.context.set_global_composite_operation(operation) {
+            error!("Unknown composite operation {} {:#?}", operation, err);
+        }
+    }
+}
+
+#[cfg(test)]
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        RecordingRenderer::default()
+    }
+
+    pub fn commands(&self) -> Vec<DrawCommand> {
+        self.commands.borrow().clone()
+    }
+
+    fn record(&self, command: DrawCommand) {
+        self.commands.borrow_mut().push(command);
+    }
+}
+
+#[cfg(test)]
+impl Renderer for RecordingRenderer {
+    fn clear(&self, rect: &Rect) {
+        self.record(DrawCommand::Clear(rect.clone()));
+    }
+
+    fn draw_image(&self, image: &ImageElement, frame: &Rect, destination: &Rect) {
+        self.record(DrawCommand::Image {
+            image: image.name(),
+            frame: frame.clone(),
+            destination: destination.clone(),
+        });
+    }
+
+    fn draw_entire_image(&self, image: &ImageElement, position: &Point) {
+        self.record(DrawCommand::EntireImage {
+            image: image.name(),
+            position: *position,
+        });
+    }
+
+    fn draw_rect(&self, rect: &Rect) {
+        self.record(DrawCommand::Rect(rect.clone()));
+    }
+
+    fn fill_rect(&self, rect: &Rect, color: &str) {
+        self.record(DrawCommand::FillRect {
+            rect: rect.clone(),
+            color: color.to_string(),
+        });
+    }
+
+    fn draw_text(&self, text: &str, location: &Point) -> Result<()> {
+        self.record(DrawCommand::Text {
+            text: text.to_string(),
+            location: *location,
+        });
+        Ok(())
+    }
+
+    fn save(&self) {
+        self.record(DrawCommand::Save);
+    }
+
+    fn restore(&self) {
+        self.record(DrawCommand::Restore);
+    }
+
+    fn translate(&self, x: f32, y: f32) {
+        self.record(DrawCommand::Translate { x, y });
+    }
+
+    fn rotate(&self, angle: f32) {
+        self.record(DrawCommand::Rotate(angle));
+    }
+
+    fn scale(&self, x: f32, y: f32) {
+        self.record(DrawCommand::Scale { x, y });
+    }
+
+    fn set_alpha(&self, alpha: f32) {
+        self.record(DrawCommand::Alpha(alpha));
+    }
+
+    fn set_composite(&self, operation: &str) {
+        self.record(DrawCommand::Composite(operation.to_string()));
+    }
+}
+
+impl DrawParams {
+    pub fn is_identity(&self) -> bool {
+        *self == DrawParams::default()
+    }
+
+    // Applies the transform around the pivot of `bounds`, so it has to be
+    // wrapped in save/restore by the caller.
+    pub fn apply(&self, renderer: &(impl Renderer + ?Sized), bounds: &Rect) {
+        if self.alpha != 1.0 {
+            renderer.set_alpha(self.alpha);
+        }
+        if let Some(composite) = &self.composite {
+            renderer.set_composite(composite);
+        }
+
+        let scaled = self.scale != 1.0 || self.flip_x || self.flip_y;
+        if self.rotation == 0.0 && !scaled {
+            return;
+        }
+        let pivot_x = bounds.x() as f32 + bounds.width as f32 * self.pivot.0;
+        let pivot_y = bounds.y() as f32 + bounds.height as f32 * self.pivot.1;
+        let flip = |flipped: bool| if flipped { -self.scale } else { self.scale };
+        renderer.translate(pivot_x, pivot_y);
+        if self.rotation != 0.0 {
+            renderer.rotate(self.rotation);
+        }
+        if scaled {
+            renderer.scale(flip(self.flip_x), flip(self.flip_y));
+        }
+        renderer.translate(-pivot_x, -pivot_y);
+    }
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        DrawParams {
+            flip_x: false,
+            flip_y: false,
+            scale: 1.0,
+            rotation: 0.0,
+            pivot: (0.5, 0.5),
+            alpha: 1.0,
+            composite: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_params_transform_around_the_pivot_and_restore_afterwards() {
+        let renderer = RecordingRenderer::new();
+        let image = ImageElement::headless("rhb_trimmed.png", 600, 300);
+        let frame = Rect::new_from_x_y(0, 0, 60, 100);
+        let destination = Rect::new_from_x_y(100, 200, 60, 100);
+
+        renderer.draw_image_with(
+            &image,
+            &frame,
+            &destination,
+            &destination,
+            &DrawParams::default(),
+        );
+        renderer.draw_image_with(
+            &image,
+            &frame,
+            &destination,
+            &destination,
+            &DrawParams {
+                flip_x: true,
+                scale: 2.0,
+                rotation: 0.5,
+                pivot: (0.5, 1.0),
+                alpha: 0.25,
+                composite: Some("lighter".to_string()),
+                ..DrawParams::default()
+            },
+        );
+
+        let draw = DrawCommand::Image {
+            image: "rhb_trimmed.png".to_string(),
+            frame,
+            destination,
+        };
+        assert_eq!(
+            renderer.commands(),
+            vec![
+                draw.clone(),
+                DrawCommand::Save,
+                DrawCommand::Alpha(0.25),
+                DrawCommand::Composite("lighter".to_string()),
+                DrawCommand::Translate { x: 130.0, y: 300.0 },
+                DrawCommand::Rotate(0.5),
+                DrawCommand::Scale { x: -2.0, y: 2.0 },
+                DrawCommand::Translate {
+                    x: -130.0,
+                    y: -300.0
+                },
+                draw,
+                DrawCommand::Restore,
+            ]
+        );
+    }
 }
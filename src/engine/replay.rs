@@ -0,0 +1,261 @@
+use crate::engine::{InputSource, KeyState, Replay, ReplayPlayer, Seed};
+use anyhow::{anyhow, Result};
+use rand::{thread_rng, Rng};
+
+const MAGIC: &[u8; 4] = b"WTDR";
+pub const REPLAY_VERSION: u8 = 1;
+const MAX_KEYS: usize = 32;
+const FIXED_SEED_FLAG: u8 = 1;
+
+impl Seed {
+    pub fn from_config(seed: Option<u64>) -> Self {
+        match seed {
+            Some(value) => Seed { value, fixed: true },
+            None => Seed {
+                value: thread_rng().gen(),
+                fixed: false,
+            },
+        }
+    }
+}
+
+impl InputSource {
+    pub fn seed(&self) -> Seed {
+        match self {
+            InputSource::Live(recording) => recording.borrow().seed(),
+            InputSource::Replay(player) => player.seed(),
+        }
+    }
+}
+
+impl Replay {
+    pub fn new(seed: Seed) -> Self {
+        Replay {
+            seed,
+            keys: vec![],
+            runs: vec![],
+        }
+    }
+
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.runs.iter().map(|(_, ticks)| ticks).sum()
+    }
+
+    pub fn record(&mut self, keystate: &KeyState) {
+        let mut mask = 0;
+        for code in keystate.pressed() {
+            if let Some(bit) = self.key_bit(code) {
+                mask |= 1 << bit;
+            }
+        }
+
+        match self.runs.last_mut() {
+            Some((last_mask, ticks)) if *last_mask == mask => *ticks += 1,
+            _ => self.runs.push((mask, 1)),
+        }
+    }
+
+    fn key_bit(&mut self, code: &str) -> Option<usize> {
+        match self.keys.iter().position(|key| key == code) {
+            Some(bit) => Some(bit),
+            None if self.keys.len() < MAX_KEYS => {
+                self.keys.push(code.to_string());
+                Some(self.keys.len() - 1)
+            }
+            None => None,
+        }
+    }
+
+    fn keystate(&self, mask: u32) -> KeyState {
+        let mut keystate = KeyState::new();
+        self.keys
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| mask & (1 << bit) != 0)
+            .for_each(|(_, code)| keystate.set_pressed(code));
+        keystate
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(REPLAY_VERSION);
+        bytes.push(if self.seed.fixed { FIXED_SEED_FLAG } else { 0 });
+        bytes.extend_from_slice(&self.seed.value.to_le_bytes());
+        bytes.push(self.keys.len() as u8);
+        for key in &self.keys {
+            bytes.push(key.len() as u8);
+            bytes.extend_from_slice(key.as_bytes());
+        }
+        bytes.extend_from_slice(&(self.runs.len() as u32).to_le_bytes());
+        for (mask, ticks) in &self.runs {
+            bytes.extend_from_slice(&mask.to_le_bytes());
+            bytes.extend_from_slice(&ticks.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(anyhow!("Not a replay file"));
+        }
+        let version = reader.u8()?;
+        if version != REPLAY_VERSION {
+            return Err(anyhow!("Unsupported replay version {}", version));
+        }
+        let flags = reader.u8()?;
+        let seed = Seed {
+            value: reader.u64()?,
+            fixed: flags & FIXED_SEED_FLAG != 0,
+        };
+
+        let key_count = reader.u8()?;
+        let mut keys = vec![];
+        for _ in 0..key_count {
+            let len = reader.u8()? as usize;
+            let key = std::str::from_utf8(reader.take(len)?)
+                .map_err(|err| anyhow!("Invalid key code in replay {:#?}", err))?;
+            keys.push(key.to_string());
+        }
+
+        let run_count = reader.u32()?;
+        let mut runs = vec![];
+        for _ in 0..run_count {
+            runs.push((reader.u32()?, reader.u32()?));
+        }
+
+        Ok(Replay { seed, keys, runs })
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or_else(|| anyhow!("Replay is truncated at byte {}", self.position))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut buffer = [0; 4];
+        buffer.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buffer))
+    }
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            run: 0,
+            tick: 0,
+        }
+    }
+
+    pub fn seed(&self) -> Seed {
+        self.replay.seed()
+    }
+
+    pub fn next_tick(&mut self) -> Option<KeyState> {
+        let (mask, ticks) = *self.replay.runs.get(self.run)?;
+        self.tick += 1;
+        if self.tick >= ticks {
+            self.run += 1;
+            self.tick = 0;
+        }
+        Some(self.replay.keystate(mask))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystate(codes: &[&str]) -> KeyState {
+        let mut keystate = KeyState::new();
+        codes.iter().for_each(|code| keystate.set_pressed(code));
+        keystate
+    }
+
+    fn pressed(keystate: &KeyState) -> Vec<String> {
+        let mut codes: Vec<String> = keystate.pressed().cloned().collect();
+        codes.sort();
+        codes
+    }
+
+    #[test]
+    fn replay_round_trips_through_bytes_and_plays_back_every_tick() {
+        let seed = Seed {
+            value: 1234,
+            fixed: true,
+        };
+        let ticks = [
+            vec![],
+            vec!["ArrowRight"],
+            vec!["ArrowRight"],
+            vec!["ArrowRight", "Space"],
+            vec!["ArrowDown"],
+        ];
+        let mut replay = Replay::new(seed);
+        ticks
+            .iter()
+            .for_each(|codes| replay.record(&keystate(codes)));
+        assert_eq!(replay.runs.len(), 4);
+
+        let replay = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(replay.ticks(), 5);
+        let mut player = ReplayPlayer::new(replay);
+        assert_eq!(player.seed(), seed);
+        for codes in ticks.iter() {
+            assert_eq!(pressed(&player.next_tick().unwrap()), *codes);
+        }
+        assert!(player.next_tick().is_none());
+    }
+
+    #[test]
+    fn keys_tapped_between_ticks_are_recorded_for_one_tick() {
+        let mut keystate = keystate(&["ArrowRight"]);
+        keystate.end_tick();
+        let mut replay = Replay::new(Seed::from_config(Some(7)));
+        keystate.set_pressed("NewGame");
+        keystate.set_released("NewGame");
+        keystate.set_released("ArrowRight");
+        for _ in 0..2 {
+            replay.record(&keystate);
+            keystate.end_tick();
+        }
+
+        let mut player = ReplayPlayer::new(replay);
+        assert_eq!(pressed(&player.next_tick().unwrap()), vec!["NewGame"]);
+        assert_eq!(pressed(&player.next_tick().unwrap()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_truncated_data() {
+        let mut bytes = Replay::new(Seed::from_config(Some(7))).to_bytes();
+        assert!(Replay::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        bytes[MAGIC.len()] = REPLAY_VERSION + 1;
+        assert!(Replay::from_bytes(&bytes).is_err());
+    }
+}
@@ -1,32 +1,167 @@
-use crate::engine::{Cell, Renderer};
+use crate::engine::{Cell, DrawParams, ImageElement, Renderer};
 use crate::engine::{Point, Rect, Sheet, SpriteSheet};
-use serde::Deserialize;
-use web_sys::HtmlImageElement;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
 
 impl SpriteSheet {
-    pub fn new(sheet: Sheet, image: HtmlImageElement) -> Self {
+    pub fn new(sheet: Sheet, image: ImageElement) -> Self {
         SpriteSheet { sheet, image }
     }
 
+    pub fn sheet(&self) -> &Sheet {
+        &self.sheet
+    }
+
+    pub fn image(&self) -> &ImageElement {
+        &self.image
+    }
+
     pub fn cell(&self, name: &str) -> Option<&Cell> {
         self.sheet.frames.get(name)
     }
 
-    pub fn draw(&self, renderer: &Renderer, source: &Rect, destination: &Rect) {
-        renderer.draw_image(&self.image, source, destination);
+    pub fn draw(
+        &self,
+        renderer: &dyn Renderer,
+        cell: &Cell,
+        position: &Point,
+        params: &DrawParams,
+    ) {
+        cell.draw(renderer, &self.image, position, params);
     }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct SheetRect {
-    pub x: i16,
-    pub y: i16,
-    pub w: i16,
-    pub h: i16,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SheetSize {
+    pub w: i32,
+    pub h: i32,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct SheetMeta {
+    pub app: String,
+    pub version: String,
+    pub image: String,
+    pub format: String,
+    pub size: SheetSize,
+    #[serde(deserialize_with = "scale")]
+    pub scale: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Frames {
+    Hash(HashMap<String, Cell>),
+    Array(Vec<NamedCell>),
+}
+
+#[derive(Deserialize)]
+struct NamedCell {
+    filename: String,
+    #[serde(flatten)]
+    cell: Cell,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scale {
+    Text(String),
+    Number(f32),
+}
+
+// TexturePacker writes `frames` as an object keyed by file name in its hash
+// format and as a list of cells carrying a `filename` in its array format.
+pub fn frames<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Cell>, D::Error> {
+    Ok(match Frames::deserialize(deserializer)? {
+        Frames::Hash(frames) => frames,
+        Frames::Array(frames) => frames
+            .into_iter()
+            .map(|named| (named.filename, named.cell))
+            .collect(),
+    })
+}
+
+fn scale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    match Scale::deserialize(deserializer)? {
+        Scale::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        Scale::Number(number) => Ok(number),
+    }
+}
+
+impl Cell {
+    pub fn source_size(&self) -> SheetSize {
+        self.source_size.unwrap_or(SheetSize {
+            w: self.frame.w,
+            h: self.frame.h,
+        })
+    }
+
+    // Trimmed cells are offset to where their pixels sat in the untrimmed
+    // sprite, untrimmed ones have an offset of zero.
+    pub fn destination(&self, position: &Point) -> Rect {
+        let (x, y) = if self.trimmed {
+            (self.sprite_source_size.x, self.sprite_source_size.y)
+        } else {
+            (0, 0)
+        };
+        Rect::new_from_x_y(position.x + x, position.y + y, self.frame.w, self.frame.h)
+    }
+
+    // The whole sprite before it was trimmed, which stays put from one frame
+    // of an animation to the next.
+    pub fn bounds(&self, position: &Point) -> Rect {
+        let size = self.source_size();
+        Rect::new(*position, size.w, size.h)
+    }
+
+    pub fn draw(
+        &self,
+        renderer: &dyn Renderer,
+        image: &ImageElement,
+        position: &Point,
+        params: &DrawParams,
+    ) {
+        let destination = self.destination(position);
+        let bounds = self.bounds(position);
+        if self.rotated {
+            // Rotated cells are stored a quarter turn clockwise, so the packed
+            // region has its width and height swapped.
+            renderer.save();
+            params.apply(renderer, &bounds);
+            renderer.translate(destination.x() as f32, destination.bottom() as f32);
+            renderer.rotate(-FRAC_PI_2);
+            renderer.draw_image(
+                image,
+                &Rect::new_from_x_y(self.frame.x, self.frame.y, self.frame.h, self.frame.w),
+                &Rect::new_from_x_y(0, 0, self.frame.h, self.frame.w),
+            );
+            renderer.restore();
+        } else {
+            renderer.draw_image_with(
+                image,
+                &Rect::new_from_x_y(self.frame.x, self.frame.y, self.frame.w, self.frame.h),
+                &destination,
+                &bounds,
+                params,
+            );
+        }
+    }
 }
 
 impl Rect {
-    pub fn new(position: Point, width: i16, height: i16) -> Self {
+    pub fn new(position: Point, width: i32, height: i32) -> Self {
         Rect {
             position,
             width,
@@ -34,27 +169,23 @@ impl Rect {
         }
     }
 
-    pub fn new_from_x_y(x: i16, y: i16, width: i16, height: i16) -> Self {
+    pub fn new_from_x_y(x: i32, y: i32, width: i32, height: i32) -> Self {
         Rect::new(Point { x, y }, width, height)
     }
 
-    pub fn x(&self) -> i16 {
+    pub fn x(&self) -> i32 {
         self.position.x
     }
 
-    pub fn set_x(&mut self, x: i16) {
-        self.position.x = x
-    }
-
-    pub fn add_x(&mut self, x: i16) {
+    pub fn add_x(&mut self, x: i32) {
         self.position.x += x
     }
 
-    pub fn y(&self) -> i16 {
+    pub fn y(&self) -> i32 {
         self.position.y
     }
 
-    pub fn add_y(&mut self, y: i16) {
+    pub fn add_y(&mut self, y: i32) {
         self.position.y += y
     }
 
@@ -65,11 +196,170 @@ impl Rect {
             && self.bottom() > rect.y()
     }
 
-    pub fn right(&self) -> i16 {
+    pub fn right(&self) -> i32 {
         self.x() + self.width
     }
 
-    pub fn bottom(&self) -> i16 {
+    pub fn union(&self, rect: &Rect) -> Rect {
+        let x = self.x().min(rect.x());
+        let y = self.y().min(rect.y());
+        Rect::new_from_x_y(
+            x,
+            y,
+            self.right().max(rect.right()) - x,
+            self.bottom().max(rect.bottom()) - y,
+        )
+    }
+
+    pub fn bottom(&self) -> i32 {
         self.y() + self.height
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{DrawCommand, RecordingRenderer};
+
+    const HASH_SHEET: &str = r#"{"frames": {
+        "Run (1).png": {
+            "frame": {"x":10,"y":20,"w":71,"h":115},
+            "rotated": true,
+            "trimmed": true,
+            "spriteSourceSize": {"x":58,"y":8,"w":71,"h":115},
+            "sourceSize": {"w":160,"h":136}
+        }},
+        "meta": {
+            "app": "https://www.codeandweb.com/texturepacker",
+            "version": "1.0",
+            "image": "rhb_trimmed.png",
+            "format": "RGBA8888",
+            "size": {"w":1989,"h":366},
+            "scale": "0.5"
+        }
+    }"#;
+
+    const ARRAY_SHEET: &str = r#"{"frames": [
+        {
+            "filename": "1.png",
+            "frame": {"x":1,"y":132,"w":128,"h":128},
+            "rotated": false,
+            "trimmed": false,
+            "spriteSourceSize": {"x":0,"y":0,"w":128,"h":128},
+            "sourceSize": {"w":128,"h":128}
+        },
+        {
+            "filename": "2.png",
+            "frame": {"x":130,"y":132,"w":100,"h":90},
+            "rotated": false,
+            "trimmed": true,
+            "spriteSourceSize": {"x":14,"y":38,"w":100,"h":90},
+            "sourceSize": {"w":128,"h":128}
+        }],
+        "meta": {"image": "tiles.png", "scale": 1}
+    }"#;
+
+    #[test]
+    fn parses_the_hash_format_with_meta() {
+        let sheet: Sheet = serde_json::from_str(HASH_SHEET).unwrap();
+        let cell = &sheet.frames["Run (1).png"];
+        assert!(cell.rotated);
+        assert!(cell.trimmed);
+        assert_eq!(cell.source_size(), SheetSize { w: 160, h: 136 });
+        let meta = sheet.meta.unwrap();
+        assert_eq!(meta.image, "rhb_trimmed.png");
+        assert_eq!(meta.size, SheetSize { w: 1989, h: 366 });
+        assert_eq!(meta.scale, 0.5);
+    }
+
+    #[test]
+    fn parses_the_array_format_and_fills_in_missing_fields() {
+        let sheet: Sheet = serde_json::from_str(ARRAY_SHEET).unwrap();
+        let cell = &sheet.frames["1.png"];
+        assert!(!cell.rotated);
+        assert!(!cell.trimmed);
+        assert!(sheet.frames["2.png"].trimmed);
+        assert_eq!(cell.source_size(), SheetSize { w: 128, h: 128 });
+        assert_eq!(sheet.meta.unwrap().scale, 1.0);
+
+        let bare: Sheet = serde_json::from_str(
+            r#"{"frames": {"2.png": {
+                "frame": {"x":0,"y":0,"w":64,"h":32},
+                "spriteSourceSize": {"x":0,"y":0,"w":64,"h":32}
+            }}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            bare.frames["2.png"].source_size(),
+            SheetSize { w: 64, h: 32 }
+        );
+        assert!(!bare.frames["2.png"].trimmed);
+        assert!(bare.meta.is_none());
+    }
+
+    #[test]
+    fn rotated_cells_are_turned_back_into_their_trimmed_position() {
+        let sheet: Sheet = serde_json::from_str(HASH_SHEET).unwrap();
+        let renderer = RecordingRenderer::new();
+        sheet.frames["Run (1).png"].draw(
+            &renderer,
+            &ImageElement::headless("rhb_trimmed.png", 1989, 366),
+            &Point { x: 100, y: 200 },
+            &DrawParams::default(),
+        );
+
+        assert_eq!(
+            renderer.commands(),
+            vec![
+                DrawCommand::Save,
+                DrawCommand::Translate { x: 158.0, y: 323.0 },
+                DrawCommand::Rotate(-FRAC_PI_2),
+                DrawCommand::Image {
+                    image: "rhb_trimmed.png".to_string(),
+                    frame: Rect::new_from_x_y(10, 20, 115, 71),
+                    destination: Rect::new_from_x_y(0, 0, 115, 71),
+                },
+                DrawCommand::Restore,
+            ]
+        );
+    }
+
+    #[test]
+    fn flipped_frames_with_different_trims_share_a_pivot() {
+        let sheet: Sheet = serde_json::from_str(
+            r#"{"frames": {
+                "Run (1).png": {
+                    "frame": {"x":0,"y":0,"w":71,"h":115},
+                    "spriteSourceSize": {"x":58,"y":8,"w":71,"h":115},
+                    "sourceSize": {"w":160,"h":136}
+                },
+                "Run (2).png": {
+                    "frame": {"x":80,"y":0,"w":90,"h":120},
+                    "spriteSourceSize": {"x":40,"y":2,"w":90,"h":120},
+                    "sourceSize": {"w":160,"h":136}
+                }
+            }}"#,
+        )
+        .unwrap();
+        let flipped = DrawParams {
+            flip_x: true,
+            ..DrawParams::default()
+        };
+        let pivot = |name: &str| {
+            let renderer = RecordingRenderer::new();
+            sheet.frames[name].draw(
+                &renderer,
+                &ImageElement::headless("rhb_trimmed.png", 1989, 366),
+                &Point { x: 100, y: 200 },
+                &flipped,
+            );
+            renderer.commands()[1].clone()
+        };
+
+        assert_eq!(
+            pivot("Run (1).png"),
+            DrawCommand::Translate { x: 180.0, y: 268.0 }
+        );
+        assert_eq!(pivot("Run (2).png"), pivot("Run (1).png"));
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLAGS: u32 = 0xF000_0000;
+
+// A map saved by Tiled as JSON (.tmj). Only orthogonal maps with embedded
+// image collection tilesets are read, since the tiles are drawn out of a
+// packed sprite sheet by their file names.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TiledMap {
+    #[serde(rename = "tilewidth")]
+    pub tile_width: i32,
+    #[serde(rename = "tileheight")]
+    pub tile_height: i32,
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub tilesets: Vec<Tileset>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum Layer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        width: usize,
+        data: Vec<u32>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        objects: Vec<Object>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Object {
+    // Tiled 1.9 saved the object type as `class`.
+    #[serde(rename = "type", alias = "class", default)]
+    pub kind: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default)]
+    pub gid: Option<u32>,
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Tileset {
+    #[serde(rename = "firstgid")]
+    pub first_gid: u32,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub tiles: Vec<TilesetTile>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TilesetTile {
+    pub id: u32,
+    pub image: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileRef<'a> {
+    pub name: &'a str,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+fn visible() -> bool {
+    true
+}
+
+impl TiledMap {
+    pub fn check_tilesets(&self) -> Result<()> {
+        match self
+            .tilesets
+            .iter()
+            .find_map(|tileset| tileset.source.as_ref())
+        {
+            Some(source) => Err(anyhow!(
+                "Tileset {} is external, embed it in the map instead",
+                source
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Looks a global tile id up in the tileset it falls in, and names the
+    // tile by its image's file name, which is its frame in the sprite sheet.
+    pub fn tile(&self, gid: u32) -> Option<TileRef<'_>> {
+        let id = gid & !FLAGS;
+        if id == 0 {
+            return None;
+        }
+        let tileset = self
+            .tilesets
+            .iter()
+            .filter(|tileset| tileset.first_gid <= id)
+            .max_by_key(|tileset| tileset.first_gid)?;
+        let tile = tileset
+            .tiles
+            .iter()
+            .find(|tile| tile.id == id - tileset.first_gid)?;
+        Some(TileRef {
+            name: tile.image.rsplit('/').next().unwrap_or(&tile.image),
+            flip_x: gid & FLIPPED_HORIZONTALLY != 0,
+            flip_y: gid & FLIPPED_VERTICALLY != 0,
+        })
+    }
+}
+
+impl Object {
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .and_then(|property| match &property.value {
+                PropertyValue::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+    }
+
+    pub fn flag(&self, name: &str) -> Option<bool> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .and_then(|property| match &property.value {
+                PropertyValue::Bool(flag) => Some(*flag),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_are_named_by_image_and_keep_their_flips() {
+        let map: TiledMap = serde_json::from_str(
+            r#"{
+                "tilewidth": 128,
+                "tileheight": 128,
+                "layers": [
+                    {"type": "tilelayer", "width": 2, "data": [1, 2147483650]},
+                    {"type": "imagelayer", "image": "sky.png"}
+                ],
+                "tilesets": [
+                    {"firstgid": 1, "tiles": [
+                        {"id": 0, "image": "tiles/1.png"},
+                        {"id": 1, "image": "tiles/2.png"}
+                    ]},
+                    {"firstgid": 3, "tiles": [{"id": 0, "image": "13.png"}]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let tile = |name, flip_x, flip_y| {
+            Some(TileRef {
+                name,
+                flip_x,
+                flip_y,
+            })
+        };
+        assert_eq!(map.tile(0), None);
+        assert_eq!(map.tile(1), tile("1.png", false, false));
+        assert_eq!(map.tile(2_147_483_650), tile("2.png", true, false));
+        assert_eq!(
+            map.tile(3 | FLIPPED_VERTICALLY),
+            tile("13.png", false, true)
+        );
+        assert_eq!(map.tile(4), None);
+        assert!(matches!(map.layers[1], Layer::Other));
+        assert!(map.check_tilesets().is_ok());
+    }
+}
@@ -1,38 +1,74 @@
 use crate::browser;
+use crate::difficulty::{Difficulty, DifficultyCurve};
 use crate::engine;
-use crate::engine::{Audio, Image, KeyState, Point, Rect, Renderer, SpriteSheet};
+use crate::engine::tiled::TiledMap;
+use crate::engine::{
+    Animations, AssetEvent, Assets, Audio, BroadPhase, Camera, ImageElement, KeyState,
+    ParallaxBackground, ParallaxFile, Point, Rect, Renderer, Seed, SpriteSheet,
+};
 use crate::game_segments::{Obstacle, RedHatBoy};
-use crate::segments::weird_platform_and_stone;
-use crate::segments::{platform_and_stone, stone_and_platform};
+use crate::game_state::red_hat_boy_states::JumpTuning;
+use crate::segments::{SegmentFile, SegmentLibrary};
+use crate::solvability;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use futures::channel::mpsc::UnboundedReceiver;
-use rand::{thread_rng, Rng};
+use futures::channel::mpsc::UnboundedSender;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::cell::Cell;
 use std::rc::Rc;
-use web_sys::HtmlImageElement;
 
-pub const HEIGHT: i16 = 600;
-const TIMELINE_MINIMUM: i16 = 1000;
-const OBSTACLE_BUFFER: i16 = 20;
+pub const WIDTH: i32 = 600;
+pub const HEIGHT: i32 = 600;
+const TIMELINE_MINIMUM: i32 = 1000;
+// How far the camera trails the boy, which keeps him where he starts on screen.
+const CAMERA_OFFSET: i32 = -20;
+const PAUSED_OVERLAY: &str = "rgba(255, 255, 255, 0.6)";
+// How long the dust from a footstep takes to settle, in ticks at the default
+// rate.
+const DUST_TICKS: f32 = 12.0;
+// The key the New Game button taps, so a restart is replayed like any other
+// input.
+const NEW_GAME_KEY: &str = "NewGame";
 
 #[async_trait(?Send)]
 pub trait Game {
-    async fn initialize(&self) -> Result<Box<dyn Game>>;
+    async fn initialize(&self, progress: UnboundedSender<AssetEvent>) -> Result<Box<dyn Game>>;
     fn update(&mut self, keystate: &KeyState);
-    fn draw(&self, render: &Renderer);
+    fn draw(&self, render: &dyn Renderer, alpha: f32);
+    fn set_paused(&mut self, paused: bool);
+    // Lets go of everything the game started outside the canvas once the loop
+    // has stopped for good.
+    fn shutdown(&mut self);
 }
 
 pub struct Walk {
     boy: RedHatBoy,
-    backgrounds: [Image; 2],
+    background: ParallaxBackground,
     obstacle_sheet: Rc<SpriteSheet>,
-    obstacles: Vec<Box<dyn Obstacle>>,
-    stone: HtmlImageElement,
-    timeline: i16,
+    obstacles: BroadPhase<Box<dyn Obstacle>>,
+    segments: SegmentLibrary,
+    difficulty: Difficulty,
+    stone: ImageElement,
+    timeline: i32,
+    camera: Camera,
+    dust: Vec<Dust>,
+    rng: ChaCha8Rng,
+    seed: Seed,
+}
+
+// A puff kicked up where the boy's foot came down, fading as it ages.
+struct Dust {
+    position: Point,
+    age: f32,
 }
 
 pub struct WalkTheDog {
     machine: Option<WalkTheDogStateMachine>,
+    seed: Seed,
+    paused: bool,
+    level: Rc<Cell<u32>>,
+    tick_scale: f32,
 }
 
 enum WalkTheDogStateMachine {
@@ -49,76 +85,114 @@ struct Ready;
 struct Walking {
     // score: UnboundedReceiver<()>,
 }
-struct GameOver {
-    new_game_event: UnboundedReceiver<()>,
-}
+struct GameOver;
 
 impl Walk {
     fn new(
         boy: RedHatBoy,
-        stone: HtmlImageElement,
-        backgrounds: [Image; 2],
+        stone: ImageElement,
+        background: ParallaxBackground,
         obstacle_sheet: Rc<SpriteSheet>,
+        segments: SegmentLibrary,
+        curve: Rc<DifficultyCurve>,
+        seed: Seed,
     ) -> Self {
-        let starting_obstacles = stone_and_platform(stone.clone(), obstacle_sheet.clone(), 0);
+        let camera = Camera::new(Point {
+            x: boy.pos_x() - CAMERA_OFFSET,
+            y: 0,
+        });
+        let starting_obstacles =
+            segments
+                .opening()
+                .obstacles(stone.clone(), obstacle_sheet.clone(), 0);
         let timeline = rightmost(&starting_obstacles);
+        let mut obstacles = BroadPhase::default();
+        place(&mut obstacles, starting_obstacles);
         Walk {
             boy,
-            backgrounds,
-            obstacles: starting_obstacles,
+            background,
+            obstacles,
             obstacle_sheet,
+            segments,
+            difficulty: Difficulty::new(curve),
             stone,
             timeline,
+            camera,
+            dust: vec![],
+            rng: ChaCha8Rng::seed_from_u64(seed.value),
+            seed,
         }
     }
 
-    fn reset(walk: Self) -> Self {
+    fn reset(mut walk: Self) -> Self {
+        let seed = Seed {
+            value: if walk.seed.fixed {
+                walk.seed.value
+            } else {
+                walk.rng.gen()
+            },
+            fixed: walk.seed.fixed,
+        };
         Walk::new(
             RedHatBoy::reset(walk.boy),
             walk.stone,
-            walk.backgrounds,
+            walk.background,
             walk.obstacle_sheet,
+            walk.segments,
+            walk.difficulty.curve(),
+            seed,
         )
     }
 
     fn generate_next_segment(&mut self) {
-        let mut rng = thread_rng();
-        let next_segment = rng.gen_range(0..3);
-
-        let mut next_obstacles = match next_segment {
-            0 => stone_and_platform(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            1 => platform_and_stone(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            2 => weird_platform_and_stone(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            _ => vec![],
-        };
-        self.timeline = rightmost(&next_obstacles);
-        self.obstacles.append(&mut next_obstacles);
+        let next_obstacles = self
+            .segments
+            .choose(&mut self.rng, self.difficulty.level())
+            .map(|segment| {
+                segment.obstacles(
+                    self.stone.clone(),
+                    self.obstacle_sheet.clone(),
+                    self.timeline + self.difficulty.obstacle_buffer(),
+                )
+            })
+            .unwrap_or_default();
+        self.timeline = rightmost(&next_obstacles).max(self.timeline);
+        place(&mut self.obstacles, next_obstacles);
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        self.backgrounds.iter().for_each(|background| {
-            background.draw(renderer);
-        });
-        self.boy.draw(renderer);
-        self.obstacles.iter().for_each(|obstacle| {
-            obstacle.draw(renderer);
+    fn kick_up_dust(&mut self) {
+        let tick_scale = self.boy.tick_scale();
+        self.dust.iter_mut().for_each(|puff| puff.age += tick_scale);
+        self.dust.retain(|puff| puff.age < DUST_TICKS);
+        self.dust.extend(
+            self.boy
+                .footsteps()
+                .iter()
+                .map(|&position| Dust { position, age: 0.0 }),
+        );
+    }
+
+    fn follow_boy(&mut self) {
+        self.camera.move_to(Point {
+            x: self.boy.pos_x() - CAMERA_OFFSET,
+            y: 0,
         });
     }
 
-    fn velocity(&self) -> i16 {
-        -self.boy.walking_speed()
+    fn draw(&self, renderer: &dyn Renderer, alpha: f32) {
+        let (camera_x, _) = self.camera.interpolated_position(alpha);
+        self.background.draw(renderer, camera_x);
+        self.camera.draw(renderer, alpha, || {
+            self.dust.iter().for_each(|puff| puff.draw(renderer));
+            self.boy.draw(renderer, alpha);
+            self.obstacles.iter().for_each(|obstacle| {
+                obstacle.draw(renderer);
+            });
+        });
+        let level = format!("Level {}", self.difficulty.level() + 1);
+        if let Err(err) = renderer.draw_text(&level, &Point { x: 20, y: 40 }) {
+            error!("Could not draw the level {:#?}", err);
+        }
     }
 
     fn knocked_out(&self) -> bool {
@@ -126,62 +200,110 @@ impl Walk {
     }
 }
 
+impl Dust {
+    fn draw(&self, renderer: &dyn Renderer) {
+        let opacity = 0.5 * (1.0 - self.age / DUST_TICKS);
+        let size = 8 + self.age as i32;
+        renderer.fill_rect(
+            &Rect::new_from_x_y(
+                self.position.x - size / 2,
+                self.position.y - size / 2,
+                size,
+                size / 2,
+            ),
+            &format!("rgba(222, 200, 160, {:.2})", opacity),
+        );
+    }
+}
+
 impl WalkTheDog {
-    pub fn new() -> Self {
-        WalkTheDog { machine: None }
+    pub fn new(seed: Seed, level: Rc<Cell<u32>>, tick_scale: f32) -> Self {
+        WalkTheDog {
+            machine: None,
+            seed,
+            paused: false,
+            level,
+            tick_scale,
+        }
     }
 }
 
 #[async_trait(?Send)]
 impl Game for WalkTheDog {
-    async fn initialize(&self) -> Result<Box<dyn Game>> {
+    async fn initialize(&self, progress: UnboundedSender<AssetEvent>) -> Result<Box<dyn Game>> {
         match self.machine {
             None => {
-                let sheet = browser::fetch_json("rhb_trimmed.json").await?;
-                let sheet = serde_wasm_bindgen::from_value(sheet).unwrap();
-
                 let audio = Audio::new()?;
-                let music = audio.load_sound("background_song.mp3").await?;
-                audio.play_looping_sound(&music, 0.1)?;
-
-                let jump_sound = audio.load_sound("SFX_Jump_23.mp3").await?;
-                let die_sound = audio.load_sound("die.wav").await?;
-                let rhb = RedHatBoy::new(
-                    sheet,
-                    engine::image::load_image("rhb_trimmed.png").await?,
+                let assets = Assets::load("assets.json", &audio, &progress).await?;
+                audio.play_looping_sound(&assets.sound("music")?, 0.1)?;
+
+                let rhb_sheet = assets.sprite_sheet("rhb")?;
+                let animations: Animations = assets.json("rhb_animations")?;
+                RedHatBoy::check_frames(rhb_sheet.sheet(), &animations)?;
+                let jump: JumpTuning = assets.json("jump")?;
+                jump.check()?;
+                let mut rhb = RedHatBoy::new(
+                    Rc::new(rhb_sheet.sheet().clone()),
+                    rhb_sheet.image().clone(),
+                    Rc::new(animations),
+                    jump,
                     audio,
-                    jump_sound,
-                    die_sound,
+                    assets.sound("jump")?,
+                    assets.sound("die")?,
                 );
+                rhb.set_tick_scale(self.tick_scale);
 
-                let background = engine::image::load_image("BG.png").await?;
-                let stone = engine::image::load_image("Stone.png").await?;
-
-                let tiles = browser::fetch_json("tiles.json").await?;
-                let tiles = serde_wasm_bindgen::from_value(tiles).unwrap();
-                let sprite_sheet = Rc::new(SpriteSheet::new(
-                    tiles,
-                    engine::image::load_image("tiles.png").await?,
-                ));
+                let parallax: ParallaxFile = assets.json("parallax")?;
+                parallax.check()?;
+                let stone = assets.image("stone")?;
+                let sprite_sheet = assets.sprite_sheet("tiles")?;
+                let curve: DifficultyCurve = assets.json("difficulty")?;
+                curve.check()?;
+                let mut segments = SegmentLibrary::new(
+                    assets.json::<SegmentFile>("segments")?,
+                    &sprite_sheet,
+                    |key| assets.json::<TiledMap>(key),
+                )?;
+                // Every segment has to be passable at every speed on the
+                // curve, since a level can change while it is on screen.
+                let probes: Vec<(RedHatBoy, i32)> = curve
+                    .levels()
+                    .iter()
+                    .map(|level| {
+                        let mut probe = rhb.probe();
+                        probe.run_at(level.running_speed);
+                        let offset = solvability::run_up(&probe, level.obstacle_buffer);
+                        (probe, offset)
+                    })
+                    .collect();
+                let unsolvable = segments.retain(|segment| {
+                    probes.iter().all(|(probe, offset)| {
+                        solvability::solvable(
+                            probe,
+                            &segment.obstacles(stone.clone(), sprite_sheet.clone(), *offset),
+                        )
+                    })
+                })?;
+                if !unsolvable.is_empty() {
+                    log!("Left out segments nobody can get past {:?}", unsolvable);
+                }
 
                 let machine = WalkTheDogStateMachine::new(Walk::new(
                     rhb,
                     stone,
-                    [
-                        Image::new(background.clone(), Point { x: 0, y: 0 }),
-                        Image::new(
-                            background.clone(),
-                            Point {
-                                x: background.width() as i16,
-                                y: 0,
-                            },
-                        ),
-                    ],
+                    ParallaxBackground::new(parallax.layers(&assets)?, WIDTH),
                     sprite_sheet,
+                    segments,
+                    Rc::new(curve),
+                    self.seed,
                 ));
 
                 Ok(Box::new(WalkTheDog {
                     machine: Some(machine),
+                    seed: self.seed,
+                    paused: false,
+                    level: self.level.clone(),
+                    tick_scale: self.tick_scale,
                 }))
             }
             Some(_) => Err(anyhow!("Error: Game is already initialized!")),
@@ -190,20 +312,54 @@ impl Game for WalkTheDog {
 
     fn update(&mut self, keystate: &KeyState) {
         if let Some(machine) = self.machine.take() {
-            self.machine.replace(machine.update(keystate));
+            let machine = machine.update(keystate);
+            self.level.set(machine.walk().difficulty.level());
+            self.machine.replace(machine);
         }
         assert!(self.machine.is_some());
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        renderer.clear(&Rect::new_from_x_y(0, 0, 600, HEIGHT));
+    fn draw(&self, renderer: &dyn Renderer, alpha: f32) {
+        renderer.clear(&Rect::new_from_x_y(0, 0, WIDTH, HEIGHT));
         if let Some(machine) = &self.machine {
-            machine.draw(renderer);
+            machine.draw(renderer, alpha);
+        }
+        if self.paused {
+            renderer.fill_rect(&Rect::new_from_x_y(0, 0, WIDTH, HEIGHT), PAUSED_OVERLAY);
+            if let Err(err) = renderer.draw_text("Paused", &Point { x: 260, y: 280 }) {
+                error!("Could not draw pause overlay {:#?}", err);
+            }
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if let Some(machine) = &self.machine {
+            let audio = machine.walk().boy.audio();
+            let result = if paused {
+                audio.suspend()
+            } else {
+                audio.resume()
+            };
+            if let Err(err) = result {
+                error!("Could not toggle audio {:#?}", err);
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(machine) = &self.machine {
+            if let Err(err) = machine.walk().boy.audio().close() {
+                error!("Could not close audio {:#?}", err);
+            }
+        }
+        if let Err(err) = browser::hide_ui() {
+            error!("Could not hide the game's UI {:#?}", err);
         }
     }
 }
 
-fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i16 {
+fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i32 {
     obstacle_list
         .iter()
         .map(|obstacle| obstacle.right())
@@ -211,6 +367,14 @@ fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i16 {
         .unwrap_or(0)
 }
 
+fn place(broad_phase: &mut BroadPhase<Box<dyn Obstacle>>, obstacles: Vec<Box<dyn Obstacle>>) {
+    broad_phase.extend(
+        obstacles
+            .into_iter()
+            .map(|obstacle| (obstacle.bounding_box(), obstacle)),
+    );
+}
+
 impl WalkTheDogStateMachine {
     fn new(walk: Walk) -> Self {
         WalkTheDogStateMachine::Ready(WalkTheDogState::new(walk))
@@ -220,28 +384,31 @@ impl WalkTheDogStateMachine {
         match self {
             WalkTheDogStateMachine::Ready(state) => state.update(keystate).into(),
             WalkTheDogStateMachine::Walking(state) => state.update(keystate).into(),
-            WalkTheDogStateMachine::GameOver(state) => state.update().into(),
+            WalkTheDogStateMachine::GameOver(state) => state.update(keystate).into(),
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
+    fn draw(&self, renderer: &dyn Renderer, alpha: f32) {
         match self {
-            WalkTheDogStateMachine::Ready(state) => state.draw(renderer),
-            WalkTheDogStateMachine::Walking(state) => state.draw(renderer),
-            WalkTheDogStateMachine::GameOver(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Ready(state) => state.draw(renderer, alpha),
+            WalkTheDogStateMachine::Walking(state) => state.draw(renderer, alpha),
+            // Nothing moves once the game is over, so there is nothing to blend.
+            WalkTheDogStateMachine::GameOver(state) => state.draw(renderer, 1.0),
         }
     }
-}
 
-impl GameOver {
-    fn new_game_pressed(&mut self) -> bool {
-        matches!(self.new_game_event.try_next(), Ok(Some(())))
+    fn walk(&self) -> &Walk {
+        match self {
+            WalkTheDogStateMachine::Ready(state) => &state.walk,
+            WalkTheDogStateMachine::Walking(state) => &state.walk,
+            WalkTheDogStateMachine::GameOver(state) => &state.walk,
+        }
     }
 }
 
 impl<T> WalkTheDogState<T> {
-    fn draw(&self, renderer: &Renderer) {
-        self.walk.draw(renderer);
+    fn draw(&self, renderer: &dyn Renderer, alpha: f32) {
+        self.walk.draw(renderer, alpha);
     }
 }
 
@@ -260,6 +427,7 @@ impl WalkTheDogState<Ready> {
 
     fn update(mut self, keystate: &KeyState) -> ReadyEndState {
         self.walk.boy.update();
+        self.walk.follow_boy();
         if keystate.is_pressed("ArrowRight") {
             ReadyEndState::Complete(self.start_running())
         } else {
@@ -274,6 +442,8 @@ impl WalkTheDogState<Ready> {
         //    .unwrap();
 
         self.run_right();
+        let speed = self.walk.difficulty.running_speed();
+        self.walk.boy.run_at(speed);
         WalkTheDogState {
             _state: Walking {},
             walk: self.walk,
@@ -294,34 +464,29 @@ impl WalkTheDogState<Walking> {
     fn update(mut self, keystate: &KeyState) -> WalkingEndState {
         if keystate.is_pressed("Space") {
             self.walk.boy.jump();
+        } else {
+            self.walk.boy.release_jump();
         }
         if keystate.is_pressed("ArrowDown") {
             self.walk.boy.slide();
         }
         self.walk.boy.update();
-
-        let walking_speed = self.walk.velocity();
-
-        let [first_background, second_background] = &mut self.walk.backgrounds;
-        first_background.move_horizontally(walking_speed);
-        second_background.move_horizontally(walking_speed);
-        if first_background.right() < 0 {
-            first_background.set_x(second_background.right());
+        self.walk.kick_up_dust();
+        if self.walk.difficulty.update(self.walk.boy.pos_x()) {
+            let speed = self.walk.difficulty.running_speed();
+            self.walk.boy.run_at(speed);
         }
-        if second_background.right() < 0 {
-            second_background.set_x(first_background.right());
-        }
-
-        self.walk.obstacles.retain(|obstacle| obstacle.right() > 0);
-        self.walk.obstacles.iter_mut().for_each(|obstacle| {
-            obstacle.move_horizontally(walking_speed);
+        let reach = self.walk.boy.reach();
+        self.walk.obstacles.query(&reach).for_each(|obstacle| {
             obstacle.check_intersection(&mut self.walk.boy);
         });
+        self.walk.follow_boy();
+
+        let left = self.walk.camera.position().x;
 
-        if self.walk.timeline < TIMELINE_MINIMUM {
+        self.walk.obstacles.remove_left_of(left);
+        if self.walk.timeline - left < TIMELINE_MINIMUM {
             self.walk.generate_next_segment();
-        } else {
-            self.walk.timeline += walking_speed;
         }
 
         if self.walk.knocked_out() {
@@ -332,15 +497,14 @@ impl WalkTheDogState<Walking> {
     }
 
     fn end_game(self) -> WalkTheDogState<GameOver> {
-        let button = browser::draw_ui("<button id='new_game'>New Game</button>")
+        log!("Game over on seed {}", self.walk.seed.value);
+        browser::draw_ui("<button id='new_game'>New Game</button>")
             .and_then(|_unit| browser::find_html_element_by_id("new_game"))
-            .map(engine::button::add_click_handler)
+            .map(|button| engine::button::add_key_handler(button, NEW_GAME_KEY))
             .unwrap();
 
         WalkTheDogState {
-            _state: GameOver {
-                new_game_event: button,
-            },
+            _state: GameOver,
             walk: self.walk,
         }
     }
@@ -352,8 +516,8 @@ enum GameOverEndState {
 }
 
 impl WalkTheDogState<GameOver> {
-    fn update(mut self) -> GameOverEndState {
-        if self._state.new_game_pressed() {
+    fn update(self, keystate: &KeyState) -> GameOverEndState {
+        if keystate.is_pressed(NEW_GAME_KEY) {
             GameOverEndState::Complete(self.new_game())
         } else {
             GameOverEndState::Continue(self)
@@ -419,28 +583,36 @@ impl From<WalkTheDogState<GameOver>> for WalkTheDogStateMachine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::engine::{Sheet, Sound};
-    use futures::channel::mpsc::unbounded;
+    use crate::engine::sprites::SheetRect;
+    use crate::engine::{
+        Cell, DrawCommand, GameLoop, Image, InputSource, LoopConfig, ParallaxLayer,
+        RecordingRenderer, Replay, ReplayPlayer, Sheet, Sound,
+    };
+    use crate::game_segments::Barrier;
+    use std::cell::RefCell;
     use std::collections::HashMap;
-    use web_sys::{AudioBuffer, AudioBufferOptions};
+    use std::time::Instant;
+    use web_sys::{AudioBuffer, AudioBufferOptions, HtmlImageElement};
 
     use wasm_bindgen_test::wasm_bindgen_test;
 
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
     #[wasm_bindgen_test]
     fn test_transition_from_game_over_to_new_game() {
-        let (_, receiver) = unbounded();
-        let image = HtmlImageElement::new().unwrap();
+        let image = ImageElement::from(HtmlImageElement::new().unwrap());
         let audio = Audio::new().unwrap();
         let options = AudioBufferOptions::new(1, 3000.0);
         let sound = Sound {
-            buffer: AudioBuffer::new(&options).unwrap(),
+            buffer: Some(AudioBuffer::new(&options).unwrap()),
         };
         let rhb = RedHatBoy::new(
-            Sheet {
+            Rc::new(Sheet {
                 frames: HashMap::new(),
-            },
+                meta: None,
+            }),
             image.clone(),
+            Rc::new(Animations::default()),
+            serde_json::from_str(include_str!("../static/jump.json")).unwrap(),
             audio,
             sound.clone(),
             sound,
@@ -448,19 +620,42 @@ mod tests {
         let sprite_sheet = SpriteSheet::new(
             Sheet {
                 frames: HashMap::new(),
+                meta: None,
             },
             image.clone(),
         );
         let walk = Walk {
             boy: rhb,
-            backgrounds: [
-                Image::new(image.clone(), Point { x: 0, y: 0 }),
-                Image::new(image.clone(), Point { x: 0, y: 0 }),
-            ],
-            obstacles: vec![],
+            background: ParallaxBackground::new(
+                vec![ParallaxLayer::new(image.clone(), 1.0, 0, true)],
+                WIDTH,
+            ),
+            obstacles: BroadPhase::default(),
             obstacle_sheet: Rc::new(sprite_sheet),
+            segments: SegmentLibrary::new(
+                serde_json::from_str(r#"{"segments": [{"name": "empty"}]}"#).unwrap(),
+                &SpriteSheet::new(
+                    Sheet {
+                        frames: HashMap::new(),
+                        meta: None,
+                    },
+                    image.clone(),
+                ),
+                |key| Err(anyhow!("No map named {}", key)),
+            )
+            .unwrap(),
+            difficulty: Difficulty::new(Rc::new(
+                serde_json::from_str(
+                    r#"{"levels": [{"distance": 0, "runningSpeed": 4, "obstacleBuffer": 20}]}"#,
+                )
+                .unwrap(),
+            )),
             stone: image.clone(),
             timeline: 0,
+            camera: Camera::new(Point { x: 0, y: 0 }),
+            dust: vec![],
+            rng: ChaCha8Rng::seed_from_u64(0),
+            seed: Seed::from_config(Some(0)),
         };
 
         let document = browser::document().unwrap();
@@ -472,9 +667,7 @@ mod tests {
         browser::draw_ui("<p>This is the UI</p>").unwrap();
 
         let state = WalkTheDogState {
-            _state: GameOver {
-                new_game_event: receiver,
-            },
+            _state: GameOver,
             walk: walk,
         };
 
@@ -482,4 +675,452 @@ mod tests {
         let ui = browser::find_html_element_by_id("ui").unwrap();
         assert_eq!(ui.child_element_count(), 0);
     }
+
+    #[wasm_bindgen_test]
+    fn a_replay_restarts_after_a_game_over_like_the_recording() {
+        browser::document()
+            .unwrap()
+            .body()
+            .unwrap()
+            .insert_adjacent_html("afterbegin", "<div id='ui'></div>")
+            .unwrap();
+        let seed = Seed::from_config(Some(0));
+        let game = || {
+            let mut walk = headless_walk(0);
+            walk.boy = boy_with_every_clip(Audio::headless());
+            WalkTheDog {
+                machine: Some(WalkTheDogStateMachine::new(walk)),
+                seed,
+                paused: false,
+                level: Rc::new(std::cell::Cell::new(0)),
+                tick_scale: 1.0,
+            }
+        };
+        let snapshot = |game: &WalkTheDog| {
+            let machine = game.machine.as_ref().unwrap();
+            let phase = match machine {
+                WalkTheDogStateMachine::Ready(_) => "ready",
+                WalkTheDogStateMachine::Walking(_) => "walking",
+                WalkTheDogStateMachine::GameOver(_) => "game over",
+            };
+            (
+                phase,
+                machine.walk().boy.pos_x(),
+                machine.walk().boy.pos_y(),
+            )
+        };
+
+        let mut live = game();
+        let mut recording = Replay::new(seed);
+        let mut keystate = KeyState::new();
+        keystate.set_pressed("ArrowRight");
+        let mut trace = vec![];
+        let mut waited = 0;
+        for _ in 0..1000 {
+            if snapshot(&live).0 == "game over" {
+                waited += 1;
+                if waited == 30 {
+                    // What clicking New Game does between two ticks.
+                    keystate.set_pressed(NEW_GAME_KEY);
+                    keystate.set_released(NEW_GAME_KEY);
+                }
+            }
+            recording.record(&keystate);
+            live.update(&keystate);
+            keystate.end_tick();
+            trace.push(snapshot(&live));
+        }
+        assert!(trace
+            .windows(2)
+            .any(|ticks| ticks[0].0 == "game over" && ticks[1].0 == "ready"));
+
+        let mut player = ReplayPlayer::new(Replay::from_bytes(&recording.to_bytes()).unwrap());
+        let mut replayed = game();
+        let replayed_trace: Vec<_> = trace
+            .iter()
+            .map(|_| {
+                replayed.update(&player.next_tick().unwrap());
+                snapshot(&replayed)
+            })
+            .collect();
+        assert_eq!(replayed_trace, trace);
+    }
+
+    // Hands the loop a game that is already set up.
+    struct Preloaded(RefCell<Option<WalkTheDog>>);
+
+    #[async_trait(?Send)]
+    impl Game for Preloaded {
+        async fn initialize(
+            &self,
+            _progress: UnboundedSender<AssetEvent>,
+        ) -> Result<Box<dyn Game>> {
+            let game = self
+                .0
+                .borrow_mut()
+                .take()
+                .ok_or_else(|| anyhow!("Error: Game is already initialized!"))?;
+            Ok(Box::new(game))
+        }
+
+        fn update(&mut self, _keystate: &KeyState) {}
+
+        fn draw(&self, _renderer: &dyn Renderer, _alpha: f32) {}
+
+        fn set_paused(&mut self, _paused: bool) {}
+
+        fn shutdown(&mut self) {}
+    }
+
+    #[wasm_bindgen_test]
+    async fn a_stopped_game_leaves_no_audio_running() {
+        browser::document()
+            .unwrap()
+            .body()
+            .unwrap()
+            .insert_adjacent_html(
+                "afterbegin",
+                "<div id='ui'></div><canvas id='canvas' width='600' height='600'></canvas>",
+            )
+            .unwrap();
+        let audio = Audio::new().unwrap();
+        let music = Sound {
+            buffer: Some(AudioBuffer::new(&AudioBufferOptions::new(1, 3000.0)).unwrap()),
+        };
+        audio.play_looping_sound(&music, 0.1).unwrap();
+        let mut walk = headless_walk(0);
+        walk.boy = boy_with_every_clip(audio.clone());
+        let seed = Seed::from_config(Some(0));
+        let game = WalkTheDog {
+            machine: Some(WalkTheDogStateMachine::new(walk)),
+            seed,
+            paused: false,
+            level: Rc::new(std::cell::Cell::new(0)),
+            tick_scale: 1.0,
+        };
+
+        let mut handle = GameLoop::start(
+            Preloaded(RefCell::new(Some(game))),
+            InputSource::Replay(ReplayPlayer::new(Replay::new(seed))),
+            LoopConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert!(audio.running());
+        handle.stop().unwrap();
+        // The context only reports itself closed once the task closing it
+        // has run.
+        let closed = js_sys::Promise::new(&mut |resolve, _reject| {
+            browser::window()
+                .unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, 100)
+                .unwrap();
+        });
+        wasm_bindgen_futures::JsFuture::from(closed).await.unwrap();
+
+        assert!(!audio.running());
+    }
+
+    fn headless_walk(seed: u64) -> Walk {
+        let sheet_rect = |x, y, w, h| SheetRect { x, y, w, h };
+        let mut frames = HashMap::new();
+        ["Idle (1).png", "Run (1).png"].iter().for_each(|name| {
+            frames.insert(
+                name.to_string(),
+                Cell {
+                    frame: sheet_rect(10, 20, 60, 100),
+                    sprite_source_size: sheet_rect(5, 8, 60, 100),
+                    rotated: false,
+                    trimmed: true,
+                    source_size: None,
+                },
+            );
+        });
+        let rhb = RedHatBoy::new(
+            Rc::new(Sheet { frames, meta: None }),
+            ImageElement::headless("rhb_trimmed.png", 600, 300),
+            Rc::new(
+                serde_json::from_str(
+                    r#"{
+                        "Idle": {"frames": ["Idle (1).png"], "frameDuration": 3},
+                        "Run": {"frames": ["Run (1).png"], "frameDuration": 3}
+                    }"#,
+                )
+                .unwrap(),
+            ),
+            serde_json::from_str(include_str!("../static/jump.json")).unwrap(),
+            Audio::headless(),
+            Sound::headless(),
+            Sound::headless(),
+        );
+        let background = ImageElement::headless("BG.png", 800, 600);
+        let mut tiles = HashMap::new();
+        ["1.png", "3.png", "13.png", "14.png", "15.png", "18.png"]
+            .iter()
+            .for_each(|name| {
+                tiles.insert(
+                    name.to_string(),
+                    Cell {
+                        frame: sheet_rect(0, 0, 128, 93),
+                        sprite_source_size: sheet_rect(0, 0, 128, 93),
+                        rotated: false,
+                        trimmed: false,
+                        source_size: None,
+                    },
+                );
+            });
+        let sprite_sheet = SpriteSheet::new(
+            Sheet {
+                frames: tiles,
+                meta: None,
+            },
+            ImageElement::headless("tiles.png", 600, 600),
+        );
+        let segments = SegmentLibrary::new(
+            serde_json::from_str(include_str!("../static/segments.json")).unwrap(),
+            &sprite_sheet,
+            |_| {
+                serde_json::from_str(include_str!("../static/maps/two_stones_and_platform.tmj"))
+                    .map_err(Into::into)
+            },
+        )
+        .unwrap();
+        Walk::new(
+            rhb,
+            ImageElement::headless("Stone.png", 90, 54),
+            ParallaxBackground::new(vec![ParallaxLayer::new(background, 1.0, 0, true)], WIDTH),
+            Rc::new(sprite_sheet),
+            segments,
+            Rc::new(serde_json::from_str(include_str!("../static/difficulty.json")).unwrap()),
+            Seed::from_config(Some(seed)),
+        )
+    }
+
+    // The boy from the real sheet, who can do more than run, such as be
+    // knocked out.
+    fn boy_with_every_clip(audio: Audio) -> RedHatBoy {
+        RedHatBoy::new(
+            Rc::new(serde_json::from_str(include_str!("../static/rhb_trimmed.json")).unwrap()),
+            ImageElement::headless("rhb_trimmed.png", 1024, 1024),
+            Rc::new(serde_json::from_str(include_str!("../static/rhb_animations.json")).unwrap()),
+            serde_json::from_str(include_str!("../static/jump.json")).unwrap(),
+            audio,
+            Sound::headless(),
+            Sound::headless(),
+        )
+    }
+
+    fn draw_segments(seed: u64, segments: usize) -> Vec<DrawCommand> {
+        let mut walk = headless_walk(seed);
+        (0..segments).for_each(|_| walk.generate_next_segment());
+        let renderer = RecordingRenderer::new();
+        walk.draw(&renderer, 1.0);
+        renderer.commands()
+    }
+
+    #[test]
+    fn draw_emits_background_boy_and_obstacles_in_order() {
+        let game = WalkTheDog {
+            machine: Some(WalkTheDogStateMachine::new(headless_walk(0))),
+            seed: Seed::from_config(Some(0)),
+            paused: false,
+            level: Rc::new(std::cell::Cell::new(0)),
+            tick_scale: 1.0,
+        };
+
+        let renderer = RecordingRenderer::new();
+        game.draw(&renderer, 0.5);
+
+        assert_eq!(
+            renderer.commands(),
+            vec![
+                DrawCommand::Clear(Rect::new_from_x_y(0, 0, WIDTH, HEIGHT)),
+                DrawCommand::EntireImage {
+                    image: "BG.png".to_string(),
+                    position: Point { x: 0, y: 0 },
+                },
+                DrawCommand::Save,
+                DrawCommand::Translate { x: 0.0, y: 0.0 },
+                DrawCommand::Image {
+                    image: "rhb_trimmed.png".to_string(),
+                    frame: Rect::new_from_x_y(10, 20, 60, 100),
+                    destination: Rect::new_from_x_y(-15, 487, 60, 100),
+                },
+                DrawCommand::EntireImage {
+                    image: "Stone.png".to_string(),
+                    position: Point { x: 150, y: 546 },
+                },
+                DrawCommand::Image {
+                    image: "tiles.png".to_string(),
+                    frame: Rect::new_from_x_y(0, 0, 128, 93),
+                    destination: Rect::new_from_x_y(370, 420, 128, 93),
+                },
+                DrawCommand::Image {
+                    image: "tiles.png".to_string(),
+                    frame: Rect::new_from_x_y(0, 0, 128, 93),
+                    destination: Rect::new_from_x_y(498, 420, 128, 93),
+                },
+                DrawCommand::Image {
+                    image: "tiles.png".to_string(),
+                    frame: Rect::new_from_x_y(0, 0, 128, 93),
+                    destination: Rect::new_from_x_y(626, 420, 128, 93),
+                },
+                DrawCommand::Restore,
+                DrawCommand::Text {
+                    text: "Level 1".to_string(),
+                    location: Point { x: 20, y: 40 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn footsteps_in_the_run_cycle_kick_up_dust() {
+        let mut walk = headless_walk(0);
+        walk.boy = boy_with_every_clip(Audio::headless());
+        walk.obstacles = BroadPhase::default();
+        let mut walking = WalkTheDogState::new(walk).start_running();
+        let mut puffs = vec![];
+        // One pass through the eight frames of the run, three ticks each.
+        for _ in 0..24 {
+            walking = match walking.update(&KeyState::new()) {
+                WalkingEndState::Continue(walking) => Some(walking),
+                WalkingEndState::Complete(_) => None,
+            }
+            .expect("The boy should still be running");
+            puffs.extend(
+                walking
+                    .walk
+                    .dust
+                    .iter()
+                    .filter(|puff| puff.age == 0.0)
+                    .map(|puff| puff.position),
+            );
+        }
+
+        assert_eq!(puffs.len(), 2);
+        assert!(puffs.iter().all(|puff| puff.y == HEIGHT));
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_segments() {
+        assert_eq!(draw_segments(42, 20), draw_segments(42, 20));
+        assert_ne!(draw_segments(42, 20), draw_segments(43, 20));
+    }
+
+    #[test]
+    fn draw_blends_the_camera_and_boy_with_the_previous_tick() {
+        let walking = WalkTheDogState::new(headless_walk(0)).start_running();
+        let walking = match walking.update(&KeyState::new()) {
+            WalkingEndState::Continue(walking) => Some(walking),
+            WalkingEndState::Complete(_) => None,
+        }
+        .expect("The boy should still be running");
+
+        let renderer = RecordingRenderer::new();
+        walking.draw(&renderer, 0.25);
+        let commands = renderer.commands();
+
+        assert_eq!(
+            commands[0],
+            DrawCommand::EntireImage {
+                image: "BG.png".to_string(),
+                position: Point { x: -1, y: 0 },
+            }
+        );
+        assert_eq!(commands[1], DrawCommand::Save);
+        assert_eq!(commands[2], DrawCommand::Translate { x: -1.0, y: 0.0 });
+        assert!(commands.contains(&DrawCommand::Image {
+            image: "rhb_trimmed.png".to_string(),
+            frame: Rect::new_from_x_y(10, 20, 60, 100),
+            destination: Rect::new_from_x_y(-14, 487, 60, 100),
+        }));
+        assert_eq!(commands[commands.len() - 2], DrawCommand::Restore);
+    }
+
+    #[test]
+    fn an_hour_long_run_stays_in_range() {
+        const TICKS_PER_HOUR: i32 = 60 * 60 * 60;
+        let mut walking = WalkTheDogState::new(headless_walk(0)).start_running();
+
+        for _ in 0..TICKS_PER_HOUR {
+            // Only the distance matters here, so obstacles are cleared before
+            // the boy can run into them.
+            walking.walk.obstacles = BroadPhase::default();
+            walking = match walking.update(&KeyState::new()) {
+                WalkingEndState::Continue(walking) => Some(walking),
+                WalkingEndState::Complete(_) => None,
+            }
+            .expect("The boy should still be running");
+        }
+
+        let left = walking.walk.camera.position().x;
+        // The last level is reached a few minutes in, and the boy runs at
+        // its speed from then on.
+        assert_eq!(walking.walk.difficulty.level(), 3);
+        assert!(walking.walk.boy.pos_x() > 7 * TICKS_PER_HOUR - 30_000);
+        assert!(walking.walk.timeline > left);
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture` for the
+    // numbers. There are no enemies or collectibles yet, so a field of
+    // stones over the boy's head stands in for everything that would share
+    // the broad-phase with them.
+    #[test]
+    #[ignore]
+    fn benchmark_updates_with_thousands_of_obstacles() {
+        const OBSTACLES: i32 = 10_000;
+        const SPACING: i32 = 30;
+        const TICKS: usize = 1_000;
+        let stones = || -> Vec<Box<dyn Obstacle>> {
+            (0..OBSTACLES)
+                .map(|index| -> Box<dyn Obstacle> {
+                    Box::new(Barrier::new(Image::new(
+                        ImageElement::headless("Stone.png", 90, 54),
+                        Point {
+                            x: index * SPACING,
+                            y: 0,
+                        },
+                    )))
+                })
+                .collect()
+        };
+        let mut walking = WalkTheDogState::new(headless_walk(0)).start_running();
+        let mut boy = walking.walk.boy.clone();
+        walking.walk.obstacles = BroadPhase::default();
+        place(&mut walking.walk.obstacles, stones());
+        walking.walk.timeline = OBSTACLES * SPACING;
+
+        let started = Instant::now();
+        for _ in 0..TICKS {
+            walking = match walking.update(&KeyState::new()) {
+                WalkingEndState::Continue(walking) => Some(walking),
+                WalkingEndState::Complete(_) => None,
+            }
+            .expect("The boy should run under the stones");
+        }
+        let broad_phase = started.elapsed();
+
+        // The same ticks checking every obstacle, the way the walk used to.
+        let mut obstacles = stones();
+        let started = Instant::now();
+        for _ in 0..TICKS {
+            boy.update();
+            obstacles
+                .iter()
+                .for_each(|obstacle| obstacle.check_intersection(&mut boy));
+            let left = boy.pos_x() - CAMERA_OFFSET;
+            obstacles.retain(|obstacle| obstacle.right() > left);
+        }
+        let every_obstacle = started.elapsed();
+
+        println!(
+            "{} obstacles, {} ticks: {:?} a tick with the broad-phase, {:?} checking every one",
+            OBSTACLES,
+            TICKS,
+            broad_phase / TICKS as u32,
+            every_obstacle / TICKS as u32,
+        );
+        assert!(broad_phase < every_obstacle);
+    }
 }
@@ -1,23 +1,51 @@
-use crate::engine::{Audio, Cell, Image, Point, Rect, Renderer, Sheet, Sound, SpriteSheet};
-use crate::game_state::red_hat_boy_states::RedHatBoyState;
+use crate::engine::{
+    Animations, Audio, Cell, DrawParams, EngineError, Image, ImageElement, Impact, Normal, Point,
+    Rect, Renderer, Sheet, Sound, SpriteSheet,
+};
+use crate::game_state::red_hat_boy_states::{
+    JumpTuning, RedHatBoyState, Velocity, CLIPS, PLAYER_HEIGHT,
+};
 use crate::game_state::{Event, RedHatBoyStateMachine};
+use anyhow::{anyhow, Result};
+use std::cmp::Ordering;
 use std::rc::Rc;
-use web_sys::HtmlImageElement;
 
-pub const LOW_PLATFORM: i16 = 420;
-pub const FIRST_PLATFORM: i16 = 370;
+const IMPACT_EVENT: &str = "impact";
+const FOOTSTEP_EVENT: &str = "footstep";
 
+#[derive(Clone)]
 pub struct RedHatBoy {
     state_machine: RedHatBoyStateMachine,
-    sprite_sheet: Sheet,
-    image: HtmlImageElement,
+    sprite_sheet: Rc<Sheet>,
+    image: ImageElement,
+    // Where his feet came down during the last update.
+    footsteps: Vec<Point>,
+}
+
+// Everything that decides how the boy moves from here on, so two boys with
+// the same snapshot will end up in the same place.
+#[derive(PartialEq, Eq, Hash)]
+pub struct Snapshot {
+    clip: String,
+    tick: u32,
+    // The part of a tick the animation has yet to move on by, as its bits.
+    tick_carry: u32,
+    position: Point,
+    velocity: Velocity,
+    subpixel: Velocity,
+    on_ground: bool,
+    jump_buffer: u32,
+    coyote_time: u32,
 }
 
 pub trait Obstacle {
     fn check_intersection(&self, boy: &mut RedHatBoy);
-    fn draw(&self, renderer: &Renderer);
-    fn move_horizontally(&mut self, x: i16);
-    fn right(&self) -> i16;
+    fn draw(&self, renderer: &dyn Renderer);
+    fn bounding_box(&self) -> Rect;
+
+    fn right(&self) -> i32 {
+        self.bounding_box().right()
+    }
 }
 
 pub struct Platform {
@@ -25,90 +53,207 @@ pub struct Platform {
     bounding_boxes: Vec<Rect>,
     sprites: Vec<Cell>,
     position: Point,
+    solid_sides: bool,
 }
 
 pub struct Barrier {
     image: Image,
 }
 
+// Tiles that are only drawn, such as those painted on a Tiled map's layers.
+pub struct Scenery {
+    sheet: Rc<SpriteSheet>,
+    tiles: Vec<(Cell, Point, DrawParams)>,
+}
+
 impl RedHatBoy {
     pub fn new(
-        sheet: Sheet,
-        image: HtmlImageElement,
+        sheet: Rc<Sheet>,
+        image: ImageElement,
+        animations: Rc<Animations>,
+        jump: JumpTuning,
         audio: Audio,
         jump_sound: Sound,
         die_sound: Sound,
     ) -> Self {
         RedHatBoy {
             state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(
-                audio, jump_sound, die_sound,
+                animations, jump, audio, jump_sound, die_sound,
             )),
             sprite_sheet: sheet,
             image,
+            footsteps: vec![],
+        }
+    }
+
+    // A silent copy of the boy as a run starts, for trying out moves without
+    // touching the one on screen. It only makes full jumps pressed right on
+    // time, since every tick a jump could be let go of or pressed early
+    // multiplies the ways of getting anywhere, and all they add is a short
+    // hop.
+    pub fn probe(&self) -> Self {
+        let jump = self.state_machine.context().jump;
+        let mut probe = RedHatBoy::new(
+            self.sprite_sheet.clone(),
+            self.image.clone(),
+            self.state_machine.context().animator.animations(),
+            JumpTuning {
+                release_speed: jump.jump_speed,
+                buffer_ticks: 0,
+                ..jump
+            },
+            Audio::headless(),
+            Sound::headless(),
+            Sound::headless(),
+        );
+        probe.set_tick_scale(self.tick_scale());
+        probe.run_right();
+        probe
+    }
+
+    pub fn check_frames(sheet: &Sheet, animations: &Animations) -> Result<()> {
+        for name in CLIPS {
+            let clip = animations
+                .clip(name)
+                .ok_or_else(|| anyhow!("No animation clip named {}", name))?;
+            if let Some(frame) = clip
+                .frames
+                .iter()
+                .find(|frame| !sheet.frames.contains_key(*frame))
+            {
+                return Err(EngineError::MissingFrame(frame.clone()).into());
+            }
         }
+        Ok(())
     }
 
     pub fn update(&mut self) {
         self.state_machine = self.state_machine.clone().update();
+        self.footsteps.clear();
+        for event in self.state_machine.take_events() {
+            if event == IMPACT_EVENT {
+                let context = self.state_machine.context();
+                if let Err(err) = context.audio.play_sound(&context.die_sound) {
+                    log!("Error playing die sound {:#?}", err);
+                }
+            } else if event == FOOTSTEP_EVENT {
+                let feet = self.bounding_box();
+                self.footsteps.push(Point {
+                    x: feet.x() + feet.width / 2,
+                    y: feet.bottom(),
+                });
+            }
+        }
+    }
+
+    pub fn tick_scale(&self) -> f32 {
+        self.state_machine.context().tick_scale
+    }
+
+    // Moves and animates him each update as if `tick_scale` ticks at the
+    // default rate went by.
+    pub fn set_tick_scale(&mut self, tick_scale: f32) {
+        self.state_machine.set_tick_scale(tick_scale);
+    }
+
+    pub fn footsteps(&self) -> &[Point] {
+        &self.footsteps
     }
 
     pub fn reset(boy: Self) -> Self {
-        RedHatBoy::new(
+        let tick_scale = boy.tick_scale();
+        let mut new_boy = RedHatBoy::new(
             boy.sprite_sheet,
             boy.image,
+            boy.state_machine.context().animator.animations(),
+            boy.state_machine.context().jump,
             boy.state_machine.context().audio.clone(),
             boy.state_machine.context().jump_sound.clone(),
             boy.state_machine.context().die_sound.clone(),
-        )
+        );
+        new_boy.set_tick_scale(tick_scale);
+        new_boy
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
+    pub fn draw(&self, renderer: &dyn Renderer, alpha: f32) {
         let sprite = self.current_sprite().expect("Cell not found");
 
-        renderer.draw_image(
+        sprite.draw(
+            renderer,
             &self.image,
-            &Rect::new_from_x_y(
-                sprite.frame.x,
-                sprite.frame.y,
-                sprite.frame.w,
-                sprite.frame.h,
-            ),
-            &self.destination_box(),
+            &self.interpolated_position(alpha),
+            &DrawParams::default(),
         );
     }
 
+    fn interpolated_position(&self, alpha: f32) -> Point {
+        let context = self.state_machine.context();
+        let lerp = |previous: i32, current: i32| {
+            (previous as f32 + (current - previous) as f32 * alpha).round() as i32
+        };
+        Point {
+            x: lerp(context.previous_position.x, context.position.x),
+            y: lerp(context.previous_position.y, context.position.y),
+        }
+    }
+
     fn destination_box(&self) -> Rect {
+        self.destination_box_at(self.state_machine.context().position)
+    }
+
+    fn destination_box_at(&self, position: Point) -> Rect {
         let sprite = self.current_sprite().expect("Cell not found");
-        Rect::new_from_x_y(
-            self.state_machine.context().position.x + sprite.sprite_source_size.x as i16,
-            self.state_machine.context().position.y + sprite.sprite_source_size.y as i16,
-            sprite.frame.w,
-            sprite.frame.h,
-        )
+        sprite.destination(&position)
     }
 
     pub fn bounding_box(&self) -> Rect {
-        const X_OFFSET: i16 = 18;
-        const Y_OFFSET: i16 = 14;
-        const WIDTH_OFFSET: i16 = 28;
+        const X_OFFSET: i32 = 18;
+        const Y_OFFSET: i32 = 14;
+        const WIDTH_OFFSET: i32 = 28;
         let mut bounding_box = self.destination_box();
         bounding_box.add_x(X_OFFSET);
         bounding_box.width -= WIDTH_OFFSET;
         bounding_box.add_y(Y_OFFSET);
-        bounding_box.height -= Y_OFFSET;
+        // The feet stay on one line whichever frame is showing, so standing
+        // still on a platform never sinks into it.
+        bounding_box.height = self.pos_y() + PLAYER_HEIGHT - bounding_box.y();
         bounding_box
     }
 
-    fn current_sprite(&self) -> Option<&Cell> {
-        self.sprite_sheet.frames.get(&self.frame_name())
+    // Sweeps the boy's box along the last tick's move, so nothing thin can
+    // slip between where he was and where he is.
+    pub fn impact(&self, other: &Rect) -> Option<Impact> {
+        let movement = self.movement();
+        self.starting_box().sweep(movement, other)
     }
 
-    fn frame_name(&self) -> String {
-        format!(
-            "{} ({}).png",
-            self.state_machine.frame_name(),
-            (self.state_machine.context().frame / 3) + 1
-        )
+    // Everything the boy's box passed over in the last tick.
+    pub fn reach(&self) -> Rect {
+        self.starting_box().union(&self.bounding_box())
+    }
+
+    fn starting_box(&self) -> Rect {
+        let movement = self.movement();
+        let mut start = self.bounding_box();
+        start.add_x(-movement.x);
+        start.add_y(-movement.y);
+        start
+    }
+
+    fn movement(&self) -> Point {
+        let context = self.state_machine.context();
+        Point {
+            x: context.position.x - context.previous_position.x,
+            y: context.position.y - context.previous_position.y,
+        }
+    }
+
+    fn current_sprite(&self) -> Option<&Cell> {
+        let context = self.state_machine.context();
+        context
+            .animator
+            .frame(self.state_machine.clip_name())
+            .and_then(|frame| self.sprite_sheet.frames.get(frame))
     }
 
     pub fn run_right(&mut self) {
@@ -123,7 +268,26 @@ impl RedHatBoy {
         self.state_machine = self.state_machine.clone().transition(Event::Jump);
     }
 
-    pub fn land_on(&mut self, y: i16) {
+    pub fn release_jump(&mut self) {
+        self.state_machine = self.state_machine.clone().transition(Event::ReleaseJump);
+    }
+
+    pub fn run_at(&mut self, speed: i32) {
+        self.state_machine = self.state_machine.clone().transition(Event::RunAt(speed));
+    }
+
+    pub fn hit_head(&mut self, depth: i32) {
+        self.state_machine = self.state_machine.clone().transition(Event::HitHead(depth));
+    }
+
+    pub fn block(&mut self, distance: i32) {
+        self.state_machine = self
+            .state_machine
+            .clone()
+            .transition(Event::Block(distance));
+    }
+
+    pub fn land_on(&mut self, y: i32) {
         self.state_machine = self.state_machine.clone().transition(Event::Land(y));
     }
 
@@ -135,69 +299,86 @@ impl RedHatBoy {
         self.state_machine.knocked_out()
     }
 
-    pub fn pos_y(&self) -> i16 {
-        self.state_machine.context().position.y
+    pub fn hurt(&self) -> bool {
+        self.state_machine.hurt()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let context = self.state_machine.context();
+        Snapshot {
+            clip: self.state_machine.clip_name().to_string(),
+            tick: context.animator.tick(),
+            tick_carry: context.animator.carry().to_bits(),
+            position: context.position,
+            velocity: context.velocity,
+            subpixel: context.subpixel,
+            on_ground: context.on_ground,
+            jump_buffer: context.jump_buffer,
+            coyote_time: context.coyote_time,
+        }
     }
 
-    pub fn velocity_y(&self) -> i16 {
-        self.state_machine.context().velocity.y
+    pub fn audio(&self) -> &Audio {
+        &self.state_machine.context().audio
     }
 
-    pub fn walking_speed(&self) -> i16 {
-        self.state_machine.context().velocity.x
+    pub fn pos_x(&self) -> i32 {
+        self.state_machine.context().position.x
+    }
+
+    pub fn pos_y(&self) -> i32 {
+        self.state_machine.context().position.y
     }
 }
 
 impl Obstacle for Platform {
     fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if let Some(box_to_land_on) = self
+        // Only the first box the boy reaches counts, with landings winning
+        // ties, so running from one box onto the next never hits the seam.
+        let first = |impact: &Impact| (impact.time, impact.manifold.normal != Normal::Top);
+        if let Some((bounding_box, impact)) = self
             .bounding_boxes()
             .iter()
-            .find(|&bounding_box| boy.bounding_box().intersects(bounding_box))
+            .filter_map(|bounding_box| {
+                boy.impact(bounding_box)
+                    .map(|impact| (bounding_box, impact))
+            })
+            .min_by(|(_, a), (_, b)| first(a).partial_cmp(&first(b)).unwrap_or(Ordering::Equal))
         {
-            if boy.velocity_y() > 0 && boy.pos_y() < self.position.y {
-                boy.land_on(box_to_land_on.y());
-            } else {
-                boy.knock_out();
+            let depth = impact.manifold.depth;
+            match impact.manifold.normal {
+                Normal::Top => boy.land_on(bounding_box.y()),
+                Normal::Bottom => boy.hit_head(depth),
+                Normal::Left if self.solid_sides => boy.block(-depth),
+                Normal::Right if self.solid_sides => boy.block(depth),
+                Normal::Left | Normal::Right => boy.knock_out(),
             }
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
+    fn draw(&self, renderer: &dyn Renderer) {
         let mut x = 0;
         self.sprites.iter().for_each(|sprite| {
+            // Just use position and the standard widths in the tileset
             self.sheet.draw(
                 renderer,
-                &Rect::new_from_x_y(
-                    sprite.frame.x,
-                    sprite.frame.y,
-                    sprite.frame.w,
-                    sprite.frame.h,
-                ),
-                // Just use position and the standard widths in the tileset
-                &Rect::new_from_x_y(
-                    self.position.x + x,
-                    self.position.y,
-                    sprite.frame.w,
-                    sprite.frame.h,
-                ),
+                sprite,
+                &Point {
+                    x: self.position.x + x,
+                    y: self.position.y,
+                },
+                &DrawParams::default(),
             );
-            x += sprite.frame.w;
-        });
-    }
-
-    fn move_horizontally(&mut self, x: i16) {
-        self.position.x += x;
-        self.bounding_boxes.iter_mut().for_each(|bounding_box| {
-            bounding_box.set_x(bounding_box.position.x + x);
+            x += sprite.source_size().w;
         });
     }
 
-    fn right(&self) -> i16 {
+    fn bounding_box(&self) -> Rect {
         self.bounding_boxes()
-            .last()
-            .unwrap_or(&Rect::default())
-            .right()
+            .iter()
+            .cloned()
+            .reduce(|bounds, bounding_box| bounds.union(&bounding_box))
+            .unwrap_or_default()
     }
 }
 
@@ -207,6 +388,7 @@ impl Platform {
         sprite_names: &[&str],
         bounding_boxes: &[Rect],
         position: Point,
+        solid_sides: bool,
     ) -> Self {
         let sprites = sprite_names
             .iter()
@@ -230,6 +412,7 @@ impl Platform {
             position,
             sprites,
             bounding_boxes,
+            solid_sides,
         }
     }
 
@@ -240,21 +423,17 @@ impl Platform {
 
 impl Obstacle for Barrier {
     fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if boy.bounding_box().intersects(self.image.bounding_box()) {
+        if boy.impact(self.image.bounding_box()).is_some() {
             boy.knock_out();
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
+    fn draw(&self, renderer: &dyn Renderer) {
         self.image.draw(renderer);
     }
 
-    fn move_horizontally(&mut self, x: i16) {
-        self.image.move_horizontally(x);
-    }
-
-    fn right(&self) -> i16 {
-        self.image.right()
+    fn bounding_box(&self) -> Rect {
+        self.image.bounding_box().clone()
     }
 }
 
@@ -263,3 +442,135 @@ impl Barrier {
         Barrier { image }
     }
 }
+
+impl Obstacle for Scenery {
+    fn check_intersection(&self, _boy: &mut RedHatBoy) {}
+
+    fn draw(&self, renderer: &dyn Renderer) {
+        self.tiles.iter().for_each(|(cell, position, params)| {
+            self.sheet.draw(renderer, cell, position, params);
+        });
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.tiles
+            .iter()
+            .map(|(cell, position, _)| cell.destination(position))
+            .reduce(|bounds, destination| bounds.union(&destination))
+            .unwrap_or_default()
+    }
+}
+
+impl Scenery {
+    pub fn new(sheet: Rc<SpriteSheet>, tiles: Vec<(Cell, Point, DrawParams)>) -> Self {
+        Scenery { sheet, tiles }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn boy() -> RedHatBoy {
+        let sheet: Sheet =
+            serde_json::from_str(include_str!("../static/rhb_trimmed.json")).unwrap();
+        RedHatBoy::new(
+            Rc::new(sheet),
+            ImageElement::headless("rhb_trimmed.png", 1024, 1024),
+            Rc::new(serde_json::from_str(include_str!("../static/rhb_animations.json")).unwrap()),
+            serde_json::from_str(include_str!("../static/jump.json")).unwrap(),
+            Audio::headless(),
+            Sound::headless(),
+            Sound::headless(),
+        )
+        .probe()
+    }
+
+    fn platform(bounds: Rect, solid_sides: bool) -> Platform {
+        let sheet = SpriteSheet::new(
+            Sheet {
+                frames: HashMap::new(),
+                meta: None,
+            },
+            ImageElement::headless("tiles.png", 0, 0),
+        );
+        Platform::new(
+            Rc::new(sheet),
+            &[],
+            &[Rect::new_from_x_y(0, 0, bounds.width, bounds.height)],
+            bounds.position,
+            solid_sides,
+        )
+    }
+
+    #[test]
+    fn platforms_resolve_the_face_the_boy_runs_into() {
+        let mut landing = boy();
+        let feet = landing.bounding_box();
+        platform(
+            Rect::new_from_x_y(feet.x() - 50, feet.bottom() - 2, 400, 50),
+            false,
+        )
+        .check_intersection(&mut landing);
+        assert!(!landing.hurt());
+        assert_eq!(landing.bounding_box().bottom(), feet.bottom() - 2);
+
+        let mut bonked = boy();
+        bonked.jump();
+        bonked.update();
+        let head = bonked.bounding_box();
+        platform(
+            Rect::new_from_x_y(head.x() - 100, head.y() - 45, 400, 50),
+            false,
+        )
+        .check_intersection(&mut bonked);
+        assert!(!bonked.hurt());
+        assert_eq!(bonked.bounding_box().y(), head.y() + 5);
+        assert_eq!(bonked.snapshot().velocity.y, 0.0);
+
+        let wall = |boy: &RedHatBoy| {
+            let front = boy.bounding_box();
+            Rect::new_from_x_y(front.right() - 3, front.y() - 200, 100, 400)
+        };
+        let mut knocked = boy();
+        platform(wall(&knocked), false).check_intersection(&mut knocked);
+        assert!(knocked.hurt());
+
+        let mut blocked = boy();
+        let x = blocked.pos_x();
+        platform(wall(&blocked), true).check_intersection(&mut blocked);
+        assert!(!blocked.hurt());
+        assert_eq!(blocked.pos_x(), x - 3);
+    }
+
+    #[test]
+    fn running_off_a_platform_drops_the_boy_to_the_floor() {
+        let mut boy = boy();
+        let floor = boy.pos_y();
+        let feet = boy.bounding_box();
+        let ledge = platform(
+            Rect::new_from_x_y(feet.x() - 50, feet.bottom() - 100, 150, 20),
+            false,
+        );
+        boy.land_on(feet.bottom() - 100);
+        let standing = boy.pos_y();
+
+        let mut clips: Vec<String> = vec![];
+        for _ in 0..100 {
+            boy.update();
+            ledge.check_intersection(&mut boy);
+            if boy.bounding_box().x() < ledge.right() {
+                assert_eq!(boy.pos_y(), standing);
+            }
+            let clip = boy.snapshot().clip;
+            if clips.last() != Some(&clip) {
+                clips.push(clip);
+            }
+        }
+
+        assert_eq!(clips, vec!["Run", "Jump", "Run"]);
+        assert_eq!(boy.pos_y(), floor);
+        assert!(!boy.hurt());
+    }
+}
@@ -6,6 +6,7 @@ pub enum RedHatBoyStateMachine {
     Running(RedHatBoyState<Running>),
     Sliding(RedHatBoyState<Sliding>),
     Jumping(RedHatBoyState<Jumping>),
+    Airborne(RedHatBoyState<Airborne>),
     Falling(RedHatBoyState<Falling>),
     KnockedOut(RedHatBoyState<KnockedOut>),
 }
@@ -14,7 +15,11 @@ pub enum Event {
     Run,
     Slide,
     Jump,
-    Land(i16),
+    ReleaseJump,
+    Land(i32),
+    RunAt(i32),
+    HitHead(i32),
+    Block(i32),
     KnockOut,
     Update,
 }
@@ -36,32 +41,81 @@ impl RedHatBoyStateMachine {
             (RedHatBoyStateMachine::Running(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
+            (RedHatBoyStateMachine::Running(state), Event::RunAt(speed)) => {
+                state.run_at(speed).into()
+            }
+            (RedHatBoyStateMachine::Running(state), Event::HitHead(depth)) => {
+                state.hit_head(depth).into()
+            }
+            (RedHatBoyStateMachine::Running(state), Event::Block(distance)) => {
+                state.block(distance).into()
+            }
 
             (RedHatBoyStateMachine::Sliding(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::Jump) => state.buffer_jump().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::KnockOut) => state.knock_out().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
+            (RedHatBoyStateMachine::Sliding(state), Event::RunAt(speed)) => {
+                state.run_at(speed).into()
+            }
+            (RedHatBoyStateMachine::Sliding(state), Event::HitHead(depth)) => {
+                state.hit_head(depth).into()
+            }
+            (RedHatBoyStateMachine::Sliding(state), Event::Block(distance)) => {
+                state.block(distance).into()
+            }
 
             (RedHatBoyStateMachine::Jumping(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::Jump) => state.buffer_jump().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::ReleaseJump) => {
+                state.release_jump().into()
+            }
             (RedHatBoyStateMachine::Jumping(state), Event::KnockOut) => state.knock_out().into(),
             (RedHatBoyStateMachine::Jumping(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
+            (RedHatBoyStateMachine::Jumping(state), Event::RunAt(speed)) => {
+                state.run_at(speed).into()
+            }
+            (RedHatBoyStateMachine::Jumping(state), Event::HitHead(depth)) => {
+                state.hit_head(depth).into()
+            }
+            (RedHatBoyStateMachine::Jumping(state), Event::Block(distance)) => {
+                state.block(distance).into()
+            }
+
+            (RedHatBoyStateMachine::Airborne(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::Jump) => state.jump().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::Land(position)) => {
+                state.land_on(position).into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::RunAt(speed)) => {
+                state.run_at(speed).into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::HitHead(depth)) => {
+                state.hit_head(depth).into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::Block(distance)) => {
+                state.block(distance).into()
+            }
 
             (RedHatBoyStateMachine::Falling(state), Event::Update) => state.update().into(),
             _ => self,
         }
     }
 
-    pub fn frame_name(&self) -> &str {
+    pub fn clip_name(&self) -> &str {
         match self {
-            RedHatBoyStateMachine::Idle(state) => state.frame_name(),
-            RedHatBoyStateMachine::Running(state) => state.frame_name(),
-            RedHatBoyStateMachine::Sliding(state) => state.frame_name(),
-            RedHatBoyStateMachine::Jumping(state) => state.frame_name(),
-            RedHatBoyStateMachine::Falling(state) => state.frame_name(),
-            RedHatBoyStateMachine::KnockedOut(state) => state.frame_name(),
+            RedHatBoyStateMachine::Idle(state) => state.clip_name(),
+            RedHatBoyStateMachine::Running(state) => state.clip_name(),
+            RedHatBoyStateMachine::Sliding(state) => state.clip_name(),
+            RedHatBoyStateMachine::Jumping(state) => state.clip_name(),
+            RedHatBoyStateMachine::Airborne(state) => state.clip_name(),
+            RedHatBoyStateMachine::Falling(state) => state.clip_name(),
+            RedHatBoyStateMachine::KnockedOut(state) => state.clip_name(),
         }
     }
 
@@ -71,14 +125,46 @@ impl RedHatBoyStateMachine {
             RedHatBoyStateMachine::Running(state) => state.context(),
             RedHatBoyStateMachine::Sliding(state) => state.context(),
             RedHatBoyStateMachine::Jumping(state) => state.context(),
+            RedHatBoyStateMachine::Airborne(state) => state.context(),
             RedHatBoyStateMachine::Falling(state) => state.context(),
             RedHatBoyStateMachine::KnockedOut(state) => state.context(),
         }
     }
 
+    pub fn take_events(&mut self) -> Vec<String> {
+        match self {
+            RedHatBoyStateMachine::Idle(state) => state.context.animator.take_events(),
+            RedHatBoyStateMachine::Running(state) => state.context.animator.take_events(),
+            RedHatBoyStateMachine::Sliding(state) => state.context.animator.take_events(),
+            RedHatBoyStateMachine::Jumping(state) => state.context.animator.take_events(),
+            RedHatBoyStateMachine::Airborne(state) => state.context.animator.take_events(),
+            RedHatBoyStateMachine::Falling(state) => state.context.animator.take_events(),
+            RedHatBoyStateMachine::KnockedOut(state) => state.context.animator.take_events(),
+        }
+    }
+
+    pub fn set_tick_scale(&mut self, tick_scale: f32) {
+        match self {
+            RedHatBoyStateMachine::Idle(state) => state.context.tick_scale = tick_scale,
+            RedHatBoyStateMachine::Running(state) => state.context.tick_scale = tick_scale,
+            RedHatBoyStateMachine::Sliding(state) => state.context.tick_scale = tick_scale,
+            RedHatBoyStateMachine::Jumping(state) => state.context.tick_scale = tick_scale,
+            RedHatBoyStateMachine::Airborne(state) => state.context.tick_scale = tick_scale,
+            RedHatBoyStateMachine::Falling(state) => state.context.tick_scale = tick_scale,
+            RedHatBoyStateMachine::KnockedOut(state) => state.context.tick_scale = tick_scale,
+        }
+    }
+
     pub fn knocked_out(&self) -> bool {
         matches!(self, RedHatBoyStateMachine::KnockedOut(_))
     }
+
+    pub fn hurt(&self) -> bool {
+        matches!(
+            self,
+            RedHatBoyStateMachine::Falling(_) | RedHatBoyStateMachine::KnockedOut(_)
+        )
+    }
 }
 
 impl From<RedHatBoyState<Idle>> for RedHatBoyStateMachine {
@@ -105,6 +191,12 @@ impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RedHatBoyState<Airborne>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Airborne>) -> Self {
+        RedHatBoyStateMachine::Airborne(state)
+    }
+}
+
 impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
     fn from(state: RedHatBoyState<Falling>) -> Self {
         RedHatBoyStateMachine::Falling(state)
@@ -117,11 +209,22 @@ impl From<RedHatBoyState<KnockedOut>> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RunningEndState> for RedHatBoyStateMachine {
+    fn from(end_state: RunningEndState) -> Self {
+        match end_state {
+            RunningEndState::Running(running) => running.into(),
+            RunningEndState::Airborne(airborne) => airborne.into(),
+            RunningEndState::Jumping(jumping) => jumping.into(),
+        }
+    }
+}
+
 impl From<SlidingEndState> for RedHatBoyStateMachine {
     fn from(end_state: SlidingEndState) -> Self {
         match end_state {
             SlidingEndState::Complete(running) => running.into(),
             SlidingEndState::Sliding(sliding) => sliding.into(),
+            SlidingEndState::Airborne(airborne) => airborne.into(),
         }
     }
 }
@@ -135,6 +238,16 @@ impl From<JumpingEndState> for RedHatBoyStateMachine {
     }
 }
 
+impl From<AirborneEndState> for RedHatBoyStateMachine {
+    fn from(end_state: AirborneEndState) -> Self {
+        match end_state {
+            AirborneEndState::Landing(landing) => landing.into(),
+            AirborneEndState::Airborne(airborne) => airborne.into(),
+            AirborneEndState::Jumping(jumping) => jumping.into(),
+        }
+    }
+}
+
 impl From<FallingEndState> for RedHatBoyStateMachine {
     fn from(end_state: FallingEndState) -> Self {
         match end_state {
@@ -145,74 +258,172 @@ impl From<FallingEndState> for RedHatBoyStateMachine {
 }
 
 pub mod red_hat_boy_states {
-    use crate::engine::{Audio, Point, Sound};
+    use crate::engine::{Animations, Animator, Audio, Point, Sound};
     use crate::game::HEIGHT;
+    use anyhow::{anyhow, Result};
+    use serde::Deserialize;
+    use std::hash::{Hash, Hasher};
+    use std::rc::Rc;
+
+    const FLOOR: i32 = 479;
+    pub const PLAYER_HEIGHT: i32 = HEIGHT - FLOOR;
+    const STARTING_POINT: i32 = -20;
+
+    // Speeds are in pixels a tick and gravity in pixels a tick each tick, all
+    // at the default tick rate. Ticks of another length scale them.
+    const RUNNING_SPEED: f32 = 4.0;
+    const TERMINAL_VELOCITY: f32 = 20.0;
+    const GRAVITY: f32 = 1.0;
+
+    const IDLE_CLIP: &str = "Idle";
+    const RUN_CLIP: &str = "Run";
+    const SLIDING_CLIP: &str = "Slide";
+    const JUMPING_CLIP: &str = "Jump";
+    const FALLING_CLIP: &str = "Dead";
+
+    pub const CLIPS: [&str; 5] = [
+        IDLE_CLIP,
+        RUN_CLIP,
+        SLIDING_CLIP,
+        JUMPING_CLIP,
+        FALLING_CLIP,
+    ];
+
+    // How the jump feels, kept with the game's data so it can be tuned
+    // without a rebuild.
+    #[derive(Deserialize, Clone, Copy, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct JumpTuning {
+        pub jump_speed: i32,
+        // What's left of the upward speed when Space is let go early.
+        pub release_speed: i32,
+        // Ticks after running off a ledge that a jump still takes off.
+        pub coyote_ticks: u32,
+        // Ticks a jump pressed before landing is held on to.
+        pub buffer_ticks: u32,
+    }
+
+    impl JumpTuning {
+        pub fn check(&self) -> Result<()> {
+            if self.jump_speed >= 0 {
+                return Err(anyhow!("The jump speed has to point up"));
+            }
+            if self.release_speed < self.jump_speed || self.release_speed > 0 {
+                return Err(anyhow!(
+                    "Letting go of a jump can only slow it down, not speed it up"
+                ));
+            }
+            Ok(())
+        }
+    }
 
-    const FLOOR: i16 = 479;
-    const PLAYER_HEIGHT: i16 = HEIGHT - FLOOR;
-    const STARTING_POINT: i16 = -20;
+    // Compared and hashed by its bits, so it can go in a snapshot.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Velocity {
+        pub x: f32,
+        pub y: f32,
+    }
 
-    const RUNNING_SPEED: i16 = 4;
-    const JUMP_SPEED: i16 = -25;
-    const TERMINAL_VELOCITY: i16 = 20;
-    const GRAVITY: i16 = 1;
+    impl PartialEq for Velocity {
+        fn eq(&self, other: &Self) -> bool {
+            self.x.to_bits() == other.x.to_bits() && self.y.to_bits() == other.y.to_bits()
+        }
+    }
 
-    const IDLE_FRAME_NAME: &str = "Idle";
-    const RUN_FRAME_NAME: &str = "Run";
-    const SLIDING_FRAME_NAME: &str = "Slide";
-    const JUMPING_FRAME_NAME: &str = "Jump";
-    const FALLING_FRAME_NAME: &str = "Dead";
+    impl Eq for Velocity {}
 
-    const IDLE_FRAMES: u8 = 29;
-    const RUNNING_FRAMES: u8 = 23;
-    const SLIDING_FRAMES: u8 = 14;
-    const JUMPING_FRAMES: u8 = 35;
-    const FALLING_FRAMES: u8 = 29;
+    impl Hash for Velocity {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.x.to_bits().hash(state);
+            self.y.to_bits().hash(state);
+        }
+    }
 
     #[derive(Clone)]
     pub struct RedHatBoyContext {
-        pub frame: u8,
+        pub animator: Animator,
         pub position: Point,
-        pub velocity: Point,
+        pub previous_position: Point,
+        pub velocity: Velocity,
+        // The part of a pixel moved that didn't add up to a whole one yet.
+        pub subpixel: Velocity,
+        // How many ticks at the default rate each update stands for.
+        pub tick_scale: f32,
+        // Whether anything held the boy up at the end of the last tick,
+        // either the floor or a platform he was put back on.
+        pub on_ground: bool,
+        pub jump: JumpTuning,
+        pub jump_buffer: u32,
+        pub coyote_time: u32,
         pub audio: Audio,
         pub jump_sound: Sound,
         pub die_sound: Sound,
     }
 
     impl RedHatBoyContext {
-        pub fn update(mut self, frame_count: u8) -> Self {
+        pub fn update(mut self, clip: &str) -> Self {
+            self.previous_position = self.position;
+            self.jump_buffer = self.jump_buffer.saturating_sub(1);
+            self.coyote_time = self.coyote_time.saturating_sub(1);
+            // Falls the way a run of default ticks would, each of which
+            // speeds up before it moves, so any tick rate lands on the same
+            // arc.
+            let fall_speed = self.velocity.y;
             if self.velocity.y < TERMINAL_VELOCITY {
-                self.velocity.y += GRAVITY;
+                self.velocity.y =
+                    (self.velocity.y + GRAVITY * self.tick_scale).min(TERMINAL_VELOCITY);
             }
+            let speed_up = self.velocity.y - fall_speed;
 
-            if self.frame < frame_count {
-                self.frame += 1;
-            } else {
-                self.frame = 0;
-            }
+            self.animator.update(clip, self.tick_scale);
 
-            self.position.y += self.velocity.y;
+            self.position.x +=
+                whole_pixels(&mut self.subpixel.x, self.velocity.x * self.tick_scale);
+            self.position.y += whole_pixels(
+                &mut self.subpixel.y,
+                (fall_speed + self.velocity.y) / 2.0 * self.tick_scale + speed_up / 2.0,
+            );
 
             if self.position.y > FLOOR {
                 self.position.y = FLOOR;
+                self.subpixel.y = 0.0;
             }
+            self.on_ground = self.position.y == FLOOR;
 
             self
         }
 
+        // Tick counts are tuned at the default rate as well.
+        fn ticks(&self, ticks: u32) -> u32 {
+            (ticks as f32 / self.tick_scale).round() as u32
+        }
+
         fn reset_frame(mut self) -> Self {
-            self.frame = 0;
+            self.animator.reset();
             self
         }
 
-        fn set_on(mut self, position: i16) -> Self {
+        fn set_on(mut self, position: i32) -> Self {
             self.position.y = position - PLAYER_HEIGHT;
+            self.subpixel.y = 0.0;
+            self.velocity.y = 0.0;
+            self.on_ground = true;
+            self
+        }
+
+        fn push_down(mut self, distance: i32) -> Self {
+            self.position.y += distance;
+            self.velocity.y = self.velocity.y.max(0.0);
+            self
+        }
+
+        fn push_right(mut self, distance: i32) -> Self {
+            self.position.x += distance;
             self
         }
 
         fn stop(mut self) -> Self {
-            self.velocity.x = 0;
-            self.velocity.y = 0;
+            self.velocity = Velocity::default();
             self
         }
 
@@ -221,8 +432,30 @@ pub mod red_hat_boy_states {
             self
         }
 
-        fn set_vertical_velocity(mut self, y: i16) -> Self {
-            self.velocity.y = y;
+        fn set_horizontal_velocity(mut self, x: i32) -> Self {
+            self.velocity.x = x as f32;
+            self
+        }
+
+        fn launch(mut self) -> Self {
+            self.velocity.y = self.jump.jump_speed as f32;
+            self.jump_buffer = 0;
+            self.coyote_time = 0;
+            self
+        }
+
+        fn remember_jump(mut self) -> Self {
+            self.jump_buffer = self.ticks(self.jump.buffer_ticks);
+            self
+        }
+
+        fn cut_jump(mut self) -> Self {
+            self.velocity.y = self.velocity.y.max(self.jump.release_speed as f32);
+            self
+        }
+
+        fn start_coyote_time(mut self) -> Self {
+            self.coyote_time = self.ticks(self.jump.coyote_ticks);
             self
         }
 
@@ -232,13 +465,15 @@ pub mod red_hat_boy_states {
             }
             self
         }
+    }
 
-        fn play_die_sound(self) -> Self {
-            if let Err(err) = self.audio.play_sound(&self.die_sound) {
-                log!("Error playing die sound {:#?}", err);
-            }
-            self
-        }
+    // Moves the whole pixels in `distance` plus what was carried over, and
+    // carries over what's left.
+    fn whole_pixels(carry: &mut f32, distance: f32) -> i32 {
+        let distance = *carry + distance;
+        let whole = distance.floor();
+        *carry = distance - whole;
+        whole as i32
     }
 
     #[derive(Clone)]
@@ -252,23 +487,84 @@ pub mod red_hat_boy_states {
             &self.context
         }
 
-        fn update_context(&mut self, frames: u8) {
-            self.context = self.context.clone().update(frames);
+        fn update_context(&mut self, clip: &str) {
+            self.context = self.context.clone().update(clip);
+        }
+
+        pub fn run_at(self, speed: i32) -> Self {
+            RedHatBoyState {
+                context: self.context.set_horizontal_velocity(speed),
+                _state: self._state,
+            }
+        }
+
+        pub fn hit_head(self, depth: i32) -> Self {
+            RedHatBoyState {
+                context: self.context.push_down(depth),
+                _state: self._state,
+            }
+        }
+
+        // A jump pressed where it can't be taken yet goes off as soon as the
+        // boy is back on his feet, if that's soon enough.
+        pub fn buffer_jump(self) -> Self {
+            RedHatBoyState {
+                context: self.context.remember_jump(),
+                _state: self._state,
+            }
+        }
+
+        fn take_off(self) -> RedHatBoyState<Jumping> {
+            RedHatBoyState {
+                context: self.context.reset_frame().launch().play_jump_sound(),
+                _state: Jumping {},
+            }
+        }
+
+        fn fall_off(self) -> RedHatBoyState<Airborne> {
+            RedHatBoyState {
+                context: self.context.reset_frame().start_coyote_time(),
+                _state: Airborne,
+            }
+        }
+
+        // Keeps running into a wall without getting any further.
+        pub fn block(self, distance: i32) -> Self {
+            RedHatBoyState {
+                context: self.context.push_right(distance),
+                _state: self._state,
+            }
         }
     }
 
     #[derive(Copy, Clone)]
     pub struct Idle;
     impl RedHatBoyState<Idle> {
-        pub fn new(audio: Audio, jump_sound: Sound, die_sound: Sound) -> Self {
+        pub fn new(
+            animations: Rc<Animations>,
+            jump: JumpTuning,
+            audio: Audio,
+            jump_sound: Sound,
+            die_sound: Sound,
+        ) -> Self {
             RedHatBoyState {
                 context: RedHatBoyContext {
-                    frame: 0,
+                    animator: Animator::new(animations),
                     position: Point {
                         x: STARTING_POINT,
                         y: FLOOR,
                     },
-                    velocity: Point { x: 0, y: 0 },
+                    previous_position: Point {
+                        x: STARTING_POINT,
+                        y: FLOOR,
+                    },
+                    velocity: Velocity::default(),
+                    subpixel: Velocity::default(),
+                    tick_scale: 1.0,
+                    on_ground: true,
+                    jump,
+                    jump_buffer: 0,
+                    coyote_time: 0,
                     audio,
                     jump_sound,
                     die_sound,
@@ -277,12 +573,12 @@ pub mod red_hat_boy_states {
             }
         }
 
-        pub fn frame_name(&self) -> &str {
-            IDLE_FRAME_NAME
+        pub fn clip_name(&self) -> &str {
+            IDLE_CLIP
         }
 
         pub fn update(mut self) -> Self {
-            self.update_context(IDLE_FRAMES);
+            self.update_context(IDLE_CLIP);
             self
         }
 
@@ -297,13 +593,21 @@ pub mod red_hat_boy_states {
     #[derive(Copy, Clone)]
     pub struct Running;
     impl RedHatBoyState<Running> {
-        pub fn frame_name(&self) -> &str {
-            RUN_FRAME_NAME
+        pub fn clip_name(&self) -> &str {
+            RUN_CLIP
         }
 
-        pub fn update(mut self) -> Self {
-            self.update_context(RUNNING_FRAMES);
-            self
+        pub fn update(mut self) -> RunningEndState {
+            let supported = self.context.on_ground;
+            if supported && self.context.jump_buffer > 0 {
+                return RunningEndState::Jumping(self.jump().update());
+            }
+            self.update_context(RUN_CLIP);
+            if supported {
+                RunningEndState::Running(self)
+            } else {
+                RunningEndState::Airborne(self.fall_off())
+            }
         }
 
         pub fn slide(self) -> RedHatBoyState<Sliding> {
@@ -314,17 +618,10 @@ pub mod red_hat_boy_states {
         }
 
         pub fn jump(self) -> RedHatBoyState<Jumping> {
-            RedHatBoyState {
-                context: self
-                    .context
-                    .reset_frame()
-                    .set_vertical_velocity(JUMP_SPEED)
-                    .play_jump_sound(),
-                _state: Jumping {},
-            }
+            self.take_off()
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+        pub fn land_on(self, position: i32) -> RedHatBoyState<Running> {
             RedHatBoyState {
                 context: self.context.set_on(position),
                 _state: Running {},
@@ -339,21 +636,31 @@ pub mod red_hat_boy_states {
         }
     }
 
+    pub enum RunningEndState {
+        Running(RedHatBoyState<Running>),
+        Airborne(RedHatBoyState<Airborne>),
+        Jumping(JumpingEndState),
+    }
+
     pub enum SlidingEndState {
         Complete(RedHatBoyState<Running>),
         Sliding(RedHatBoyState<Sliding>),
+        Airborne(RedHatBoyState<Airborne>),
     }
 
     #[derive(Copy, Clone)]
     pub struct Sliding;
     impl RedHatBoyState<Sliding> {
-        pub fn frame_name(&self) -> &str {
-            SLIDING_FRAME_NAME
+        pub fn clip_name(&self) -> &str {
+            SLIDING_CLIP
         }
 
         pub fn update(mut self) -> SlidingEndState {
-            self.update_context(SLIDING_FRAMES);
-            if self.context.frame >= SLIDING_FRAMES {
+            let supported = self.context.on_ground;
+            self.update_context(SLIDING_CLIP);
+            if !supported {
+                SlidingEndState::Airborne(self.fall_off())
+            } else if self.context.animator.finished(SLIDING_CLIP) {
                 SlidingEndState::Complete(self.stand())
             } else {
                 SlidingEndState::Sliding(self)
@@ -367,7 +674,7 @@ pub mod red_hat_boy_states {
             }
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Sliding> {
+        pub fn land_on(self, position: i32) -> RedHatBoyState<Sliding> {
             RedHatBoyState {
                 context: self.context.set_on(position),
                 _state: Sliding {},
@@ -390,12 +697,12 @@ pub mod red_hat_boy_states {
     #[derive(Copy, Clone)]
     pub struct Jumping;
     impl RedHatBoyState<Jumping> {
-        pub fn frame_name(&self) -> &str {
-            JUMPING_FRAME_NAME
+        pub fn clip_name(&self) -> &str {
+            JUMPING_CLIP
         }
 
         pub fn update(mut self) -> JumpingEndState {
-            self.update_context(JUMPING_FRAMES);
+            self.update_context(JUMPING_CLIP);
             if self.context.position.y >= FLOOR {
                 JumpingEndState::Landing(self.land_on(HEIGHT))
             } else {
@@ -403,7 +710,64 @@ pub mod red_hat_boy_states {
             }
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+        // Letting go on the way up cuts the jump short.
+        pub fn release_jump(self) -> Self {
+            RedHatBoyState {
+                context: self.context.cut_jump(),
+                _state: Jumping,
+            }
+        }
+
+        pub fn land_on(self, position: i32) -> RedHatBoyState<Running> {
+            RedHatBoyState {
+                context: self.context.reset_frame().set_on(position),
+                _state: Running,
+            }
+        }
+
+        pub fn knock_out(self) -> RedHatBoyState<Falling> {
+            RedHatBoyState {
+                context: self.context.reset_frame().stop(),
+                _state: Falling {},
+            }
+        }
+    }
+
+    pub enum AirborneEndState {
+        Landing(RedHatBoyState<Running>),
+        Airborne(RedHatBoyState<Airborne>),
+        Jumping(RedHatBoyState<Jumping>),
+    }
+
+    // Dropping without having jumped, after running or sliding off the end
+    // of a platform.
+    #[derive(Copy, Clone)]
+    pub struct Airborne;
+    impl RedHatBoyState<Airborne> {
+        pub fn clip_name(&self) -> &str {
+            JUMPING_CLIP
+        }
+
+        // Just off a ledge there's still time to jump from it. After that
+        // the jump waits for the landing.
+        pub fn jump(self) -> AirborneEndState {
+            if self.context.coyote_time > 0 {
+                AirborneEndState::Jumping(self.take_off())
+            } else {
+                AirborneEndState::Airborne(self.buffer_jump())
+            }
+        }
+
+        pub fn update(mut self) -> AirborneEndState {
+            self.update_context(JUMPING_CLIP);
+            if self.context.position.y >= FLOOR {
+                AirborneEndState::Landing(self.land_on(HEIGHT))
+            } else {
+                AirborneEndState::Airborne(self)
+            }
+        }
+
+        pub fn land_on(self, position: i32) -> RedHatBoyState<Running> {
             RedHatBoyState {
                 context: self.context.reset_frame().set_on(position),
                 _state: Running,
@@ -426,13 +790,13 @@ pub mod red_hat_boy_states {
     #[derive(Copy, Clone)]
     pub struct Falling;
     impl RedHatBoyState<Falling> {
-        pub fn frame_name(&self) -> &str {
-            FALLING_FRAME_NAME
+        pub fn clip_name(&self) -> &str {
+            FALLING_CLIP
         }
 
         pub fn update(mut self) -> FallingEndState {
-            self.context = self.context.update(FALLING_FRAMES);
-            if self.context.frame >= FALLING_FRAMES {
+            self.context = self.context.update(FALLING_CLIP);
+            if self.context.animator.finished(FALLING_CLIP) {
                 FallingEndState::KnockedOut(self.knock_out())
             } else {
                 FallingEndState::Falling(self)
@@ -441,7 +805,7 @@ pub mod red_hat_boy_states {
 
         pub fn knock_out(self) -> RedHatBoyState<KnockedOut> {
             RedHatBoyState {
-                context: self.context.play_die_sound(),
+                context: self.context,
                 _state: KnockedOut {},
             }
         }
@@ -450,8 +814,136 @@ pub mod red_hat_boy_states {
     #[derive(Copy, Clone)]
     pub struct KnockedOut;
     impl RedHatBoyState<KnockedOut> {
-        pub fn frame_name(&self) -> &str {
-            FALLING_FRAME_NAME
+        pub fn clip_name(&self) -> &str {
+            FALLING_CLIP
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Audio, Sound};
+    use std::rc::Rc;
+
+    fn tuning() -> JumpTuning {
+        serde_json::from_str(include_str!("../static/jump.json")).unwrap()
+    }
+
+    fn running() -> RedHatBoyStateMachine {
+        running_at(1.0)
+    }
+
+    fn running_at(tick_scale: f32) -> RedHatBoyStateMachine {
+        let mut boy: RedHatBoyStateMachine = RedHatBoyState::new(
+            Rc::new(serde_json::from_str(include_str!("../static/rhb_animations.json")).unwrap()),
+            tuning(),
+            Audio::headless(),
+            Sound::headless(),
+            Sound::headless(),
+        )
+        .run()
+        .into();
+        boy.set_tick_scale(tick_scale);
+        boy
+    }
+
+    fn jumping(machine: &RedHatBoyStateMachine) -> bool {
+        matches!(machine, RedHatBoyStateMachine::Jumping(_))
+    }
+
+    #[test]
+    fn letting_go_of_space_cuts_a_jump_short() {
+        assert!(tuning().check().is_ok());
+        let height = |held_for| {
+            let mut boy = running().transition(Event::Jump);
+            let floor = boy.context().position.y;
+            let mut top = floor;
+            for tick in 0..60 {
+                if tick >= held_for {
+                    boy = boy.transition(Event::ReleaseJump);
+                }
+                boy = boy.update();
+                top = top.min(boy.context().position.y);
+            }
+            floor - top
+        };
+
+        assert_eq!(height(1), 52);
+        assert!(height(1) < height(8));
+        assert!(height(8) < height(60));
+        assert_eq!(height(60), 300);
+    }
+
+    #[test]
+    fn a_jump_still_takes_off_just_after_leaving_a_ledge() {
+        let jump_after = |ticks| {
+            let mut boy = running();
+            if let RedHatBoyStateMachine::Running(state) = &mut boy {
+                state.context.position.y -= 100;
+                state.context.on_ground = false;
+            }
+            boy = boy.update();
+            assert!(matches!(boy, RedHatBoyStateMachine::Airborne(_)));
+            for _ in 0..ticks {
+                boy = boy.update();
+            }
+            boy.transition(Event::Jump)
+        };
+
+        assert!(jumping(&jump_after(0)));
+        assert!(jumping(&jump_after(tuning().coyote_ticks - 1)));
+        assert!(!jumping(&jump_after(tuning().coyote_ticks)));
+    }
+
+    #[test]
+    fn a_jump_pressed_just_before_landing_goes_off_on_landing() {
+        let landing = {
+            let mut boy = running().transition(Event::Jump);
+            let mut ticks = 0;
+            while jumping(&boy) {
+                boy = boy.update();
+                ticks += 1;
+            }
+            ticks
+        };
+        let jumps_again = |pressed_before: u32| {
+            let mut boy = running().transition(Event::Jump);
+            for tick in 0..landing {
+                if tick == landing - pressed_before {
+                    boy = boy.transition(Event::Jump);
+                }
+                boy = boy.update();
+            }
+            assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+            jumping(&boy.update())
+        };
+
+        assert!(jumps_again(2));
+        assert!(jumps_again(tuning().buffer_ticks - 1));
+        assert!(!jumps_again(tuning().buffer_ticks));
+    }
+
+    #[test]
+    fn a_jump_covers_the_same_ground_at_any_tick_rate() {
+        // Where a full jump lands and how high it gets, a second after it
+        // starts, at `tick_rate` ticks a second.
+        let jump = |tick_rate: u32| {
+            let mut boy = running_at(60.0 / tick_rate as f32).transition(Event::Jump);
+            let start = boy.context().position;
+            let mut top = start.y;
+            for _ in 0..tick_rate {
+                boy = boy.update();
+                top = top.min(boy.context().position.y);
+            }
+            (boy.context().position.x - start.x, start.y - top)
+        };
+
+        let (distance, height) = jump(60);
+        for tick_rate in [30, 120, 144] {
+            let (other_distance, other_height) = jump(tick_rate);
+            assert!((other_distance - distance).abs() <= 1, "{}", tick_rate);
+            assert!((other_height - height).abs() <= 1, "{}", tick_rate);
         }
     }
 }
@@ -1,12 +1,24 @@
+// Declared ahead of `browser` so the wasm_bindgen expansions in here keep
+// using std's `panic!` instead of the console one exported by `browser`.
+mod config;
+mod session;
 #[macro_use]
 mod browser;
+mod difficulty;
 mod engine;
 mod game;
 mod game_segments;
 mod game_state;
 mod segments;
+mod solvability;
 mod sound;
 
+use anyhow::Error;
+pub use config::GameConfig;
+use engine::{CanvasRenderer, EngineError, ErrorScene, InputSource, Replay, ReplayPlayer, Seed};
+pub use session::GameSession;
+use std::cell::RefCell;
+use std::rc::Rc;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 use web_sys::console;
 
@@ -19,11 +31,67 @@ pub fn main_js() -> Result<(), JsValue> {
     ));
     log!("testing log! macro :). Hello world again!");
 
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn run(config: GameConfig) -> Result<GameSession, JsValue> {
+    let input = match config.replay() {
+        Some(bytes) => InputSource::Replay(ReplayPlayer::new(
+            Replay::from_bytes(bytes)
+                .map_err(|err| JsValue::from_str(&format!("Could not load replay {:#?}", err)))?,
+        )),
+        None => InputSource::Live(Rc::new(RefCell::new(Replay::new(Seed::from_config(
+            config.seed(),
+        ))))),
+    };
+    let seed = input.seed();
+    let loop_config = config.loop_config();
+    let session = GameSession::new(&input);
+
+    let started = session.clone();
     browser::spawn_local(async move {
-        engine::GameLoop::start(game::WalkTheDog::new())
+        loop {
+            match engine::GameLoop::start(
+                game::WalkTheDog::new(seed, started.level_cell(), loop_config.tick_scale()),
+                input.clone(),
+                loop_config,
+            )
             .await
-            .expect("could not start game loop");
+            {
+                Ok(handle) => {
+                    if let Err(err) = started.attach(handle) {
+                        error!("Could not stop the game loop {:#?}", err);
+                    }
+                    break;
+                }
+                Err(err) => {
+                    if let Err(err) = report_failure(&config, &err).await {
+                        error!("Could not show the error screen {:#?}", err);
+                        break;
+                    }
+                }
+            }
+        }
     });
 
-    Ok(())
+    Ok(session)
+}
+
+async fn report_failure(config: &GameConfig, err: &Error) -> anyhow::Result<()> {
+    error!("Could not start the game {:#?}", err);
+    if let Some(on_error) = config.on_error() {
+        let kind = EngineError::find(err).map_or("unknown", EngineError::kind);
+        if let Err(err) = on_error.call2(
+            &JsValue::NULL,
+            &JsValue::from_str(kind),
+            &JsValue::from_str(&format!("{:#}", err)),
+        ) {
+            error!("onError hook threw {:#?}", err);
+        }
+    }
+    let renderer = CanvasRenderer {
+        context: browser::context()?,
+    };
+    ErrorScene::new(err).wait_for_retry(&renderer).await
 }
@@ -1,132 +1,488 @@
-use crate::engine::{Image, Point, Rect, SpriteSheet};
-use crate::game_segments::{Barrier, Obstacle, Platform, FIRST_PLATFORM, LOW_PLATFORM};
+use crate::engine::tiled::{Layer, Object, TiledMap};
+use crate::engine::{Cell, DrawParams, EngineError, Image, ImageElement, Point, Rect, SpriteSheet};
+use crate::game_segments::{Barrier, Obstacle, Platform, Scenery};
+use anyhow::{anyhow, Context, Result};
+use rand::Rng;
+use serde::Deserialize;
 use std::rc::Rc;
-use web_sys::HtmlImageElement;
-
-const STONE_ON_GROUND: i16 = 546;
-const FLOATING_PLATFORM_SPRITES: [&str; 3] = ["13.png", "14.png", "15.png"];
-const FLOATING_PLATFORM_BOUNDING_BOXES: [Rect; 3] = [
-    Rect {
-        position: Point { x: 0, y: 0 },
-        width: 60,
-        height: 54,
-    },
-    Rect {
-        position: Point { x: 60, y: 0 },
-        width: 384 - (60 * 2),
-        height: 93,
-    },
-    Rect {
-        position: Point { x: 384 - 60, y: 0 },
-        width: 60,
-        height: 54,
-    },
-];
-const WATERY_PLATFORM_SPRITES: [&str; 3] = ["1.png", "18.png", "3.png"];
-const WATERY_PLATFORM_BOUNDING_BOXES: [Rect; 3] = [
-    Rect {
-        position: Point { x: 0, y: 0 },
-        width: 60,
-        height: 54,
-    },
-    Rect {
-        position: Point { x: 60, y: 0 },
-        width: 384 - (60 * 2),
-        height: 93,
+
+const PLATFORM_OBJECT: &str = "platform";
+const BARRIER_OBJECT: &str = "barrier";
+const OPENING_TAG: &str = "start";
+
+#[derive(Deserialize)]
+pub struct SegmentFile {
+    segments: Vec<SegmentDefinition>,
+}
+
+// A segment as written in the library file, either as a list of obstacles
+// or as the key of a Tiled map in the asset manifest.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentDefinition {
+    name: String,
+    #[serde(default = "default_weight")]
+    weight: u32,
+    #[serde(default)]
+    min_difficulty: u32,
+    #[serde(default)]
+    weight_per_level: u32,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    map: Option<String>,
+    #[serde(default)]
+    obstacles: Vec<ObstacleDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ObstacleDefinition {
+    Barrier {
+        x: i32,
+        y: i32,
     },
-    Rect {
-        position: Point { x: 384 - 60, y: 0 },
-        width: 60,
-        height: 54,
+    #[serde(rename_all = "camelCase")]
+    Platform {
+        x: i32,
+        y: i32,
+        sprites: Vec<String>,
+        bounding_boxes: Vec<Bounds>,
+        // Platforms knock the boy down when he runs into their sides,
+        // unless they are solid and stop him instead.
+        #[serde(default)]
+        solid_sides: bool,
     },
-];
-
-pub fn stone_and_platform(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    vec![
-        Box::new(Barrier::new(Image::new(
-            stone,
-            Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
-                y: STONE_ON_GROUND,
-            },
-        ))),
-        Box::new(create_floating_platform(
-            sprite_sheet,
-            Point {
-                x: offset_x + FIRST_PLATFORM,
-                y: LOW_PLATFORM,
-            },
-        )),
-    ]
 }
 
-pub fn platform_and_stone(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    vec![
-        Box::new(Barrier::new(Image::new(
-            stone,
-            Point {
-                x: offset_x + FIRST_PLATFORM,
-                y: STONE_ON_GROUND,
-            },
-        ))),
-        Box::new(create_floating_platform(
-            sprite_sheet,
-            Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
-                y: LOW_PLATFORM,
-            },
-        )),
-    ]
+#[derive(Deserialize)]
+pub struct Bounds {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+// A segment read once at startup, ready to be placed along the timeline as
+// often as it comes up.
+pub struct Segment {
+    name: String,
+    weight: u32,
+    min_difficulty: u32,
+    weight_per_level: u32,
+    tags: Vec<String>,
+    scenery: Vec<(Cell, Point, DrawParams)>,
+    pieces: Vec<Piece>,
+}
+
+enum Piece {
+    Platform(Vec<String>, Point, Vec<Rect>, bool),
+    Barrier(Point),
+}
+
+pub struct SegmentLibrary {
+    segments: Vec<Segment>,
+}
+
+fn default_weight() -> u32 {
+    1
 }
 
-pub fn weird_platform_and_stone(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    vec![
-        Box::new(Barrier::new(Image::new(
-            stone,
-            Point {
-                x: offset_x + FIRST_PLATFORM,
-                y: STONE_ON_GROUND,
-            },
-        ))),
-        Box::new(create_weird_floating_platform(
-            sprite_sheet,
-            Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
-                y: LOW_PLATFORM,
-            },
-        )),
-    ]
+impl SegmentLibrary {
+    pub fn new(
+        file: SegmentFile,
+        sheet: &SpriteSheet,
+        map: impl Fn(&str) -> Result<TiledMap>,
+    ) -> Result<Self> {
+        let segments = file
+            .segments
+            .iter()
+            .map(|definition| {
+                Segment::new(definition, sheet, &map)
+                    .with_context(|| format!("Could not build the {} segment", definition.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if segments.is_empty() {
+            return Err(anyhow!("The segment library has no segments"));
+        }
+        Ok(SegmentLibrary { segments })
+    }
+
+    // Drops the segments `keep` turns down and names them.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Segment) -> bool) -> Result<Vec<String>> {
+        let (kept, dropped): (Vec<Segment>, Vec<Segment>) =
+            self.segments.drain(..).partition(|segment| keep(segment));
+        if kept.is_empty() {
+            return Err(anyhow!("Every segment in the library was turned down"));
+        }
+        self.segments = kept;
+        Ok(dropped.into_iter().map(|segment| segment.name).collect())
+    }
+
+    // Runs open on the first segment tagged as a start, so the player always
+    // meets the same obstacles first.
+    pub fn opening(&self) -> &Segment {
+        self.segments
+            .iter()
+            .find(|segment| segment.tags.iter().any(|tag| tag == OPENING_TAG))
+            .unwrap_or(&self.segments[0])
+    }
+
+    pub fn choose(&self, rng: &mut impl Rng, difficulty: u32) -> Option<&Segment> {
+        let available = || {
+            self.segments
+                .iter()
+                .filter(move |segment| segment.min_difficulty <= difficulty)
+        };
+        let total: u32 = available()
+            .map(|segment| segment.weight_at(difficulty))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        available().find(|segment| {
+            let weight = segment.weight_at(difficulty);
+            if pick < weight {
+                true
+            } else {
+                pick -= weight;
+                false
+            }
+        })
+    }
+}
+
+impl Segment {
+    fn new(
+        definition: &SegmentDefinition,
+        sheet: &SpriteSheet,
+        map: impl Fn(&str) -> Result<TiledMap>,
+    ) -> Result<Self> {
+        let mut segment = Segment {
+            name: definition.name.clone(),
+            weight: definition.weight,
+            min_difficulty: definition.min_difficulty,
+            weight_per_level: definition.weight_per_level,
+            tags: definition.tags.clone(),
+            scenery: vec![],
+            pieces: vec![],
+        };
+        if let Some(key) = &definition.map {
+            segment.add_map(&map(key)?, sheet)?;
+        }
+        for obstacle in &definition.obstacles {
+            match obstacle {
+                ObstacleDefinition::Barrier { x, y } => {
+                    segment.pieces.push(Piece::Barrier(Point { x: *x, y: *y }));
+                }
+                ObstacleDefinition::Platform {
+                    x,
+                    y,
+                    sprites,
+                    bounding_boxes,
+                    solid_sides,
+                } => {
+                    let bounding_boxes = bounding_boxes
+                        .iter()
+                        .map(|bounds| {
+                            Rect::new_from_x_y(bounds.x, bounds.y, bounds.width, bounds.height)
+                        })
+                        .collect();
+                    segment.add_platform(
+                        sheet,
+                        sprites.clone(),
+                        Point { x: *x, y: *y },
+                        bounding_boxes,
+                        *solid_sides,
+                    )?;
+                }
+            }
+        }
+        Ok(segment)
+    }
+
+    // Segments grow more likely with every level past the one they unlock at.
+    fn weight_at(&self, difficulty: u32) -> u32 {
+        self.weight + self.weight_per_level * (difficulty - self.min_difficulty)
+    }
+
+    fn add_map(&mut self, map: &TiledMap, sheet: &SpriteSheet) -> Result<()> {
+        map.check_tilesets()?;
+        for layer in &map.layers {
+            match layer {
+                Layer::Tiles {
+                    width,
+                    data,
+                    visible: true,
+                } => {
+                    let width = (*width).max(1);
+                    for (index, gid) in data.iter().enumerate().filter(|(_, gid)| **gid != 0) {
+                        let (cell, params) = tile(map, sheet, *gid)?;
+                        // Tiles taller than the grid stand on the bottom of
+                        // their grid cell, the way Tiled draws them.
+                        let position = Point {
+                            x: (index % width) as i32 * map.tile_width,
+                            y: (index / width + 1) as i32 * map.tile_height - cell.source_size().h,
+                        };
+                        self.scenery.push((cell, position, params));
+                    }
+                }
+                Layer::Objects {
+                    objects,
+                    visible: true,
+                } => {
+                    for object in objects {
+                        self.add_object(map, sheet, object)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_object(&mut self, map: &TiledMap, sheet: &SpriteSheet, object: &Object) -> Result<()> {
+        let (x, y) = (object.x.round() as i32, object.y.round() as i32);
+        match (object.kind.as_str(), object.gid) {
+            (PLATFORM_OBJECT, _) => {
+                let sprites = object
+                    .text("sprites")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(String::from)
+                    .collect();
+                let bounds = Rect::new_from_x_y(
+                    0,
+                    0,
+                    object.width.round() as i32,
+                    object.height.round() as i32,
+                );
+                self.add_platform(
+                    sheet,
+                    sprites,
+                    Point { x, y },
+                    vec![bounds],
+                    object.flag("solidSides").unwrap_or(false),
+                )?;
+            }
+            (BARRIER_OBJECT, _) => self.pieces.push(Piece::Barrier(Point { x, y })),
+            // Tile objects are placed by their bottom left corner.
+            ("", Some(gid)) => {
+                let (cell, params) = tile(map, sheet, gid)?;
+                let position = Point {
+                    x,
+                    y: y - cell.source_size().h,
+                };
+                self.scenery.push((cell, position, params));
+            }
+            (kind, _) => return Err(anyhow!("Unknown object type '{}' in a Tiled map", kind)),
+        }
+        Ok(())
+    }
+
+    fn add_platform(
+        &mut self,
+        sheet: &SpriteSheet,
+        sprites: Vec<String>,
+        position: Point,
+        bounding_boxes: Vec<Rect>,
+        solid_sides: bool,
+    ) -> Result<()> {
+        if let Some(missing) = sprites.iter().find(|name| sheet.cell(name).is_none()) {
+            return Err(EngineError::MissingFrame(missing.clone()).into());
+        }
+        self.pieces.push(Piece::Platform(
+            sprites,
+            position,
+            bounding_boxes,
+            solid_sides,
+        ));
+        Ok(())
+    }
+
+    pub fn obstacles(
+        &self,
+        stone: ImageElement,
+        sprite_sheet: Rc<SpriteSheet>,
+        offset_x: i32,
+    ) -> Vec<Box<dyn Obstacle>> {
+        let offset = |position: &Point| Point {
+            x: position.x + offset_x,
+            y: position.y,
+        };
+        let mut obstacles: Vec<Box<dyn Obstacle>> = vec![];
+        if !self.scenery.is_empty() {
+            let tiles = self
+                .scenery
+                .iter()
+                .map(|(cell, position, params)| (cell.clone(), offset(position), params.clone()))
+                .collect();
+            obstacles.push(Box::new(Scenery::new(sprite_sheet.clone(), tiles)));
+        }
+        self.pieces.iter().for_each(|piece| match piece {
+            Piece::Platform(sprites, position, bounding_boxes, solid_sides) => {
+                let sprites: Vec<&str> = sprites.iter().map(String::as_str).collect();
+                obstacles.push(Box::new(Platform::new(
+                    sprite_sheet.clone(),
+                    &sprites,
+                    bounding_boxes,
+                    offset(position),
+                    *solid_sides,
+                )));
+            }
+            Piece::Barrier(position) => {
+                obstacles.push(Box::new(Barrier::new(Image::new(
+                    stone.clone(),
+                    offset(position),
+                ))));
+            }
+        });
+        obstacles
+    }
 }
 
-fn create_floating_platform(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Platform {
-    Platform::new(
-        sprite_sheet,
-        &FLOATING_PLATFORM_SPRITES,
-        &FLOATING_PLATFORM_BOUNDING_BOXES,
-        position,
-    )
+fn tile(map: &TiledMap, sheet: &SpriteSheet, gid: u32) -> Result<(Cell, DrawParams)> {
+    let tile = map
+        .tile(gid)
+        .ok_or_else(|| anyhow!("No tile in the map's tilesets has id {}", gid))?;
+    let cell = sheet
+        .cell(tile.name)
+        .cloned()
+        .ok_or_else(|| EngineError::MissingFrame(tile.name.to_string()))?;
+    Ok((
+        cell,
+        DrawParams {
+            flip_x: tile.flip_x,
+            flip_y: tile.flip_y,
+            ..DrawParams::default()
+        },
+    ))
 }
 
-fn create_weird_floating_platform(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Platform {
-    Platform::new(
-        sprite_sheet,
-        &WATERY_PLATFORM_SPRITES,
-        &WATERY_PLATFORM_BOUNDING_BOXES,
-        position,
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sprites::SheetRect;
+    use crate::engine::{DrawCommand, RecordingRenderer, Sheet};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn tiles() -> SpriteSheet {
+        let frames = ["1.png", "3.png", "13.png", "14.png", "15.png", "18.png"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let rect = |x| SheetRect {
+                    x,
+                    y: 0,
+                    w: 128,
+                    h: 93,
+                };
+                let cell = Cell {
+                    frame: rect(index as i32 * 128),
+                    sprite_source_size: rect(0),
+                    rotated: false,
+                    trimmed: false,
+                    source_size: None,
+                };
+                (name.to_string(), cell)
+            })
+            .collect();
+        SpriteSheet::new(
+            Sheet { frames, meta: None },
+            ImageElement::headless("tiles.png", 768, 93),
+        )
+    }
+
+    fn library(json: &str) -> Result<SegmentLibrary> {
+        SegmentLibrary::new(serde_json::from_str(json).unwrap(), &tiles(), |_| {
+            serde_json::from_str(include_str!("../static/maps/two_stones_and_platform.tmj"))
+                .map_err(Into::into)
+        })
+    }
+
+    #[test]
+    fn tiled_maps_become_scenery_platforms_and_barriers() {
+        let library = library(r#"{"segments": [{"name": "map", "map": "map"}]}"#).unwrap();
+
+        let obstacles = library.opening().obstacles(
+            ImageElement::headless("Stone.png", 90, 54),
+            Rc::new(tiles()),
+            1000,
+        );
+
+        assert_eq!(
+            obstacles
+                .iter()
+                .map(|obstacle| obstacle.right())
+                .collect::<Vec<_>>(),
+            vec![1754, 1240, 1430, 1694, 1754, 1990]
+        );
+        let renderer = RecordingRenderer::new();
+        obstacles[0].draw(&renderer);
+        assert_eq!(
+            renderer.commands()[0],
+            DrawCommand::Image {
+                image: "tiles.png".to_string(),
+                frame: Rect::new_from_x_y(256, 0, 128, 93),
+                destination: Rect::new_from_x_y(1370, 420, 128, 93),
+            }
+        );
+    }
+
+    #[test]
+    fn segments_are_chosen_by_weight_above_their_difficulty() {
+        let segments = library(include_str!("../static/segments.json")).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut with_two_stones = |difficulty| {
+            (0..300)
+                .filter_map(|_| segments.choose(&mut rng, difficulty))
+                .filter(|segment| {
+                    segment
+                        .pieces
+                        .iter()
+                        .filter(|piece| matches!(piece, Piece::Barrier(_)))
+                        .count()
+                        == 2
+                })
+                .count()
+        };
+
+        assert_eq!(segments.opening().tags, ["start", "stone", "platform"]);
+        assert_eq!(with_two_stones(0), 0);
+        assert!((40..110).contains(&with_two_stones(1)));
+        assert!(library(r#"{"segments": []}"#).is_err());
+        assert!(library(
+            r#"{"segments": [{"name": "bad", "obstacles": [
+                {"type": "platform", "x": 0, "y": 0, "sprites": ["99.png"], "boundingBoxes": []}
+            ]}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn a_seed_always_chooses_the_same_segments() {
+        let segments = library(include_str!("../static/segments.json")).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(42);
+        let chosen: Vec<&str> = (0..8)
+            .filter_map(|_| segments.choose(&mut rng, 2))
+            .map(|segment| segment.name.as_str())
+            .collect();
+        assert_eq!(
+            chosen,
+            [
+                "platform_and_stone",
+                "two_stones_and_platform",
+                "weird_platform_and_stone",
+                "two_stones_and_platform",
+                "platform_and_stone",
+                "two_stones_and_platform",
+                "platform_and_stone",
+                "platform_and_stone",
+            ]
+        );
+    }
 }
@@ -0,0 +1,85 @@
+use crate::engine::{GameLoopHandle, InputSource, Replay};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+enum LoopSlot {
+    Starting,
+    Started(GameLoopHandle),
+    Stopped,
+}
+
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct GameSession {
+    recording: Option<Rc<RefCell<Replay>>>,
+    game_loop: Rc<RefCell<LoopSlot>>,
+    level: Rc<Cell<u32>>,
+}
+
+impl GameSession {
+    pub fn new(input: &InputSource) -> Self {
+        let recording = match input {
+            InputSource::Live(recording) => Some(recording.clone()),
+            InputSource::Replay(_) => None,
+        };
+        GameSession {
+            recording,
+            game_loop: Rc::new(RefCell::new(LoopSlot::Starting)),
+            level: Rc::new(Cell::new(0)),
+        }
+    }
+
+    // Shared with the game, which keeps it at its current difficulty level.
+    pub fn level_cell(&self) -> Rc<Cell<u32>> {
+        self.level.clone()
+    }
+
+    pub fn attach(&self, mut handle: GameLoopHandle) -> anyhow::Result<()> {
+        let mut slot = self.game_loop.borrow_mut();
+        match *slot {
+            LoopSlot::Stopped => handle.stop(),
+            _ => {
+                *slot = LoopSlot::Started(handle);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl GameSession {
+    pub fn replay(&self) -> Option<Vec<u8>> {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.borrow().to_bytes())
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level.get()
+    }
+
+    pub fn pause(&self) {
+        if let LoopSlot::Started(handle) = &*self.game_loop.borrow() {
+            handle.pause();
+        }
+    }
+
+    pub fn resume(&self) {
+        if let LoopSlot::Started(handle) = &*self.game_loop.borrow() {
+            handle.resume();
+        }
+    }
+
+    pub fn stop(&self) -> Result<(), JsValue> {
+        match self.game_loop.replace(LoopSlot::Stopped) {
+            LoopSlot::Started(mut handle) => handle.stop().map_err(to_js_error),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn to_js_error(err: anyhow::Error) -> JsValue {
+    JsValue::from_str(&format!("{:#?}", err))
+}
@@ -0,0 +1,158 @@
+use crate::game_segments::{Obstacle, RedHatBoy};
+use std::collections::HashSet;
+
+// Long enough to cross any segment that fits in a few screens.
+const MAX_TICKS: usize = 2000;
+
+#[derive(Clone, Copy)]
+enum Input {
+    Nothing,
+    Jump,
+    Slide,
+}
+
+const INPUTS: [Input; 3] = [Input::Nothing, Input::Jump, Input::Slide];
+
+// Searches every way of jumping and sliding through the obstacles, a tick at
+// a time, and reports whether any of them gets the boy past the last one
+// without being knocked down. Boys that end a tick in the same state are
+// only followed once, which keeps the search to a few hundred per tick.
+pub fn solvable(boy: &RedHatBoy, obstacles: &[Box<dyn Obstacle>]) -> bool {
+    let finish = obstacles
+        .iter()
+        .map(|obstacle| obstacle.right())
+        .max()
+        .unwrap_or(i32::MIN);
+    let mut boys = vec![boy.clone()];
+    let mut seen = HashSet::new();
+    for _ in 0..MAX_TICKS {
+        let mut next = vec![];
+        for boy in &boys {
+            for input in INPUTS {
+                let mut boy = boy.clone();
+                // Space is either held or let go of on every tick.
+                match input {
+                    Input::Nothing => boy.release_jump(),
+                    Input::Jump => boy.jump(),
+                    Input::Slide => {
+                        boy.release_jump();
+                        boy.slide();
+                    }
+                }
+                boy.update();
+                obstacles
+                    .iter()
+                    .for_each(|obstacle| obstacle.check_intersection(&mut boy));
+                if boy.hurt() {
+                    continue;
+                }
+                if boy.bounding_box().x() > finish {
+                    return true;
+                }
+                if seen.insert(boy.snapshot()) {
+                    next.push(boy);
+                }
+            }
+        }
+        if next.is_empty() {
+            return false;
+        }
+        boys = next;
+    }
+    false
+}
+
+// Where the generator would start a segment for a boy who has only just got
+// past the one before it.
+pub fn run_up(boy: &RedHatBoy, obstacle_buffer: i32) -> i32 {
+    boy.bounding_box().x() + obstacle_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::DifficultyCurve;
+    use crate::engine::{Audio, Image, ImageElement, Point, Sheet, Sound, SpriteSheet};
+    use crate::game_segments::Barrier;
+    use crate::segments::SegmentLibrary;
+    use std::rc::Rc;
+
+    fn boy() -> RedHatBoy {
+        let sheet: Sheet =
+            serde_json::from_str(include_str!("../static/rhb_trimmed.json")).unwrap();
+        RedHatBoy::new(
+            Rc::new(sheet),
+            ImageElement::headless("rhb_trimmed.png", 1024, 1024),
+            Rc::new(serde_json::from_str(include_str!("../static/rhb_animations.json")).unwrap()),
+            serde_json::from_str(include_str!("../static/jump.json")).unwrap(),
+            Audio::headless(),
+            Sound::headless(),
+            Sound::headless(),
+        )
+        .probe()
+    }
+
+    fn stone() -> ImageElement {
+        ImageElement::headless("Stone.png", 90, 54)
+    }
+
+    #[test]
+    fn every_segment_in_the_library_can_be_cleared() {
+        let tiles: Sheet = serde_json::from_str(include_str!("../static/tiles.json")).unwrap();
+        let tiles = Rc::new(SpriteSheet::new(
+            tiles,
+            ImageElement::headless("tiles.png", 1024, 1024),
+        ));
+        let mut library = SegmentLibrary::new(
+            serde_json::from_str(include_str!("../static/segments.json")).unwrap(),
+            &tiles,
+            |_| {
+                serde_json::from_str(include_str!("../static/maps/two_stones_and_platform.tmj"))
+                    .map_err(Into::into)
+            },
+        )
+        .unwrap();
+
+        let curve: DifficultyCurve =
+            serde_json::from_str(include_str!("../static/difficulty.json")).unwrap();
+        let boys: Vec<(RedHatBoy, i32)> = curve
+            .levels()
+            .iter()
+            .map(|level| {
+                let mut boy = boy();
+                boy.run_at(level.running_speed);
+                let offset = run_up(&boy, level.obstacle_buffer);
+                (boy, offset)
+            })
+            .collect();
+        let unsolvable = library
+            .retain(|segment| {
+                boys.iter().all(|(boy, offset)| {
+                    solvable(boy, &segment.obstacles(stone(), tiles.clone(), *offset))
+                })
+            })
+            .unwrap();
+
+        assert_eq!(unsolvable, Vec::<String>::new());
+    }
+
+    #[test]
+    fn a_row_of_stones_longer_than_a_jump_cannot_be_cleared() {
+        let stones = |count| -> Vec<Box<dyn Obstacle>> {
+            (0..count)
+                .map(|index| -> Box<dyn Obstacle> {
+                    Box::new(Barrier::new(Image::new(
+                        stone(),
+                        Point {
+                            x: 150 + index * 90,
+                            y: 546,
+                        },
+                    )))
+                })
+                .collect()
+        };
+
+        assert!(solvable(&boy(), &stones(1)));
+        assert!(!solvable(&boy(), &stones(8)));
+    }
+}
@@ -3,7 +3,8 @@ use js_sys::ArrayBuffer;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioDestinationNode, AudioNode, GainNode,
+    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioDestinationNode, AudioNode,
+    AudioScheduledSourceNode, GainNode,
 };
 
 pub async fn decode_audio_data(
@@ -24,6 +25,24 @@ pub fn create_audio_context() -> Result<AudioContext> {
     AudioContext::new().map_err(|err| anyhow!("Could not create audio context: {:#?}", err))
 }
 
+pub fn suspend_audio_context(ctx: &AudioContext) -> Result<()> {
+    ctx.suspend()
+        .map(|_promise| ())
+        .map_err(|err| anyhow!("Could not suspend audio context: {:#?}", err))
+}
+
+pub fn resume_audio_context(ctx: &AudioContext) -> Result<()> {
+    ctx.resume()
+        .map(|_promise| ())
+        .map_err(|err| anyhow!("Could not resume audio context: {:#?}", err))
+}
+
+pub fn close_audio_context(ctx: &AudioContext) -> Result<()> {
+    ctx.close()
+        .map(|_promise| ())
+        .map_err(|err| anyhow!("Could not close audio context: {:#?}", err))
+}
+
 pub fn create_gain(ctx: &AudioContext) -> Result<GainNode> {
     ctx.create_gain()
         .map_err(|err| anyhow!("Could not create gain: {:#?}", err))
@@ -77,12 +96,19 @@ pub fn play_sound(
     buffer: &AudioBuffer,
     looping: LOOPING,
     volume: f32,
-) -> Result<()> {
+) -> Result<AudioBufferSourceNode> {
     let track_source = create_track_source(ctx, buffer, volume)?;
     if matches!(looping, LOOPING::Yes) {
         track_source.set_loop(true);
     }
     track_source
         .start()
-        .map_err(|err| anyhow!("Could not start sound!{:#?}", err))
+        .map_err(|err| anyhow!("Could not start sound!{:#?}", err))?;
+    Ok(track_source)
+}
+
+pub fn stop_sound(track_source: &AudioScheduledSourceNode) -> Result<()> {
+    track_source
+        .stop()
+        .map_err(|err| anyhow!("Could not stop sound {:#?}", err))
 }